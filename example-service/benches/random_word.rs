@@ -0,0 +1,68 @@
+//! Random word selection at 1M words, comparing the hashmap store with the
+//! previous approach of collecting every key and walking the map to the picked index.
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use example_service::stores::hashmap::HashmapStore;
+use example_service::stores::{Store, WordMetadata, WordRecord};
+use rand::Rng;
+use std::collections::HashMap;
+
+const WORD_COUNT: usize = 1_000_000;
+
+fn records() -> impl Iterator<Item = WordRecord> {
+    (0..WORD_COUNT).map(|i| WordRecord::new(format!("word-{i}"), WordMetadata::default(), "bench"))
+}
+
+fn keys_nth(words: &HashMap<String, WordRecord>) -> String {
+    let keys: Vec<_> = words.keys().cloned().collect();
+    let index = rand::rng().random_range(0..keys.len());
+    words.keys().nth(index).unwrap().to_string()
+}
+
+fn random_word(c: &mut Criterion) {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let mut group = c.benchmark_group("random_word_1m");
+
+    let uniform = HashmapStore::from_records(records());
+    group.bench_function("hashmap_store_uniform", |b| {
+        b.to_async(&runtime)
+            .iter(|| async { uniform.get_random_word().await.unwrap() })
+    });
+
+    let mut weighted = HashmapStore::from_records(records());
+    runtime.block_on(async {
+        for i in (0..WORD_COUNT).step_by(10) {
+            weighted.set_weight(format!("word-{i}"), 5.0).await.unwrap();
+        }
+    });
+    group.bench_function("hashmap_store_weighted", |b| {
+        b.to_async(&runtime)
+            .iter(|| async { weighted.get_random_word().await.unwrap() })
+    });
+
+    let mut churned = HashmapStore::from_records(records());
+    group.bench_function("hashmap_store_add_remove", |b| {
+        b.iter_batched(
+            || format!("word-{}", rand::rng().random_range(0..WORD_COUNT)),
+            |word| {
+                runtime.block_on(async {
+                    let record = churned.get_word(word.clone()).await.unwrap();
+                    churned.remove_word(word).await.unwrap();
+                    churned.add_word(record).await.unwrap();
+                })
+            },
+            BatchSize::SmallInput,
+        )
+    });
+
+    let map: HashMap<String, WordRecord> = records()
+        .map(|record| (record.word.clone(), record))
+        .collect();
+    group.sample_size(10);
+    group.bench_function("keys_nth", |b| b.iter(|| keys_nth(&map)));
+
+    group.finish();
+}
+
+criterion_group!(benches, random_word);
+criterion_main!(benches);
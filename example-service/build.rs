@@ -1,4 +1,10 @@
+use std::{env, path::PathBuf};
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    tonic_build::compile_protos("proto/word.proto")?;
+    let out_dir = PathBuf::from(env::var("OUT_DIR")?);
+    // Descriptors are kept for gRPC reflection
+    tonic_build::configure()
+        .file_descriptor_set_path(out_dir.join("word_descriptor.bin"))
+        .compile_protos(&["proto/word.proto"], &["proto"])?;
     Ok(())
 }
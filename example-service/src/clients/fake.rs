@@ -0,0 +1,181 @@
+use crate::clients::{ChainEventStream, Client, ClientError, ConnectionState};
+use crate::core::{
+    ChainLimits, ChainResult, ChainState, Core, CoreConfig, CoreError, FailurePolicy, ProbeConfig,
+};
+use crate::stores::hashmap::{HashmapStore, HashmapStoreError};
+use crate::stores::{WordMetadata, WordRecord};
+use crate::strategies::ChainStrategyKind;
+use futures::StreamExt;
+use std::convert::Infallible;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::RwLock;
+use tonic::async_trait;
+
+pub type FakeCore = Core<HashmapStore, FakeClient>;
+
+/// Client calling the core of another instance of the same process, standing in for the gRPC
+/// client in tests.
+#[derive(Clone, Debug)]
+pub struct FakeClient {
+    url: String,
+    /// Instance requests are sent to, requests failing as unavailable without one
+    core: Option<FakeCore>,
+    /// Requests fail as unavailable while set
+    down: Arc<AtomicBool>,
+    /// Requests never get an answer while set, like those sent to a host that went away
+    stalled: Arc<AtomicBool>,
+    /// States of the chain, chain_stream and next_word requests sent so far
+    received: Arc<Mutex<Vec<ChainState>>>,
+}
+
+impl FakeClient {
+    pub fn new(core: FakeCore) -> Self {
+        FakeClient {
+            url: format!("fake://{0}", core.service_id()),
+            core: Some(core),
+            down: Arc::new(AtomicBool::new(false)),
+            stalled: Arc::new(AtomicBool::new(false)),
+            received: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    pub fn set_down(&self, down: bool) {
+        self.down.store(down, Ordering::Relaxed);
+    }
+
+    pub fn set_stalled(&self, stalled: bool) {
+        self.stalled.store(stalled, Ordering::Relaxed);
+    }
+
+    pub fn received(&self) -> Vec<ChainState> {
+        self.received.lock().unwrap().clone()
+    }
+
+    async fn core(&self, state: Option<&ChainState>) -> Result<FakeCore, ClientError<Infallible>> {
+        if let Some(state) = state {
+            self.received.lock().unwrap().push(state.clone());
+        }
+        if self.stalled.load(Ordering::Relaxed) {
+            std::future::pending::<()>().await;
+        }
+        match &self.core {
+            Some(core) if !self.down.load(Ordering::Relaxed) => Ok(core.clone()),
+            _ => Err(ClientError::ServiceUnavailable),
+        }
+    }
+}
+
+/// Maps errors the way the gRPC interface and client do, through status codes.
+fn client_error(err: CoreError<HashmapStoreError, Infallible>) -> ClientError<Infallible> {
+    match err {
+        CoreError::NoConnectedServices | CoreError::Empty => {
+            ClientError::BadRequest(err.to_string())
+        }
+        CoreError::InvalidArgument(msg) => ClientError::BadRequest(msg),
+        CoreError::UnsatisfiableChain(msg) => ClientError::FailedPrecondition(msg),
+        CoreError::DeadlineExceeded => ClientError::DeadlineExceeded,
+        CoreError::HopFailed(msg) => ClientError::Aborted(msg),
+        CoreError::ClientError(err) => ClientError::Aborted(err.to_string()),
+        _ => ClientError::InternalServerError,
+    }
+}
+
+#[async_trait]
+impl Client for FakeClient {
+    type E = Infallible;
+
+    /// Clients connected by url have no instance behind them.
+    fn connect(url: String) -> Result<Self, ClientError<Infallible>> {
+        Ok(FakeClient {
+            url,
+            core: None,
+            down: Arc::new(AtomicBool::new(false)),
+            stalled: Arc::new(AtomicBool::new(false)),
+            received: Arc::new(Mutex::new(Vec::new())),
+        })
+    }
+
+    fn connection_state(&self) -> ConnectionState {
+        ConnectionState::Connected
+    }
+
+    fn get_url(&self) -> String {
+        self.url.clone()
+    }
+
+    async fn health(&mut self) -> Result<(), ClientError<Infallible>> {
+        self.core(None).await.map(|_| ())
+    }
+
+    async fn service_id(&mut self) -> Result<String, ClientError<Infallible>> {
+        self.core(None)
+            .await
+            .map(|core| core.service_id().to_string())
+    }
+
+    async fn chain(&mut self, state: ChainState) -> Result<ChainResult, ClientError<Infallible>> {
+        self.core(Some(&state))
+            .await?
+            .chain(state)
+            .await
+            .map_err(client_error)
+    }
+
+    async fn chain_stream(
+        &mut self,
+        state: ChainState,
+    ) -> Result<ChainEventStream<Infallible>, ClientError<Infallible>> {
+        Ok(self
+            .core(Some(&state))
+            .await?
+            .chain_stream(state)
+            .map(|event| event.map_err(client_error))
+            .boxed())
+    }
+
+    async fn next_word(
+        &mut self,
+        state: ChainState,
+    ) -> Result<Option<String>, ClientError<Infallible>> {
+        self.core(Some(&state))
+            .await?
+            .next_word(state)
+            .await
+            .map_err(client_error)
+    }
+}
+
+pub fn config(service_id: &str) -> CoreConfig {
+    CoreConfig {
+        service_id: service_id.to_string(),
+        chain_strategy: ChainStrategyKind::Random,
+        limits: ChainLimits {
+            max_chain_count: 100,
+            max_input_length: 100,
+            max_word_length: 64,
+        },
+        failure_policy: FailurePolicy::FailFast,
+        max_hop_retries: 2,
+        probe: ProbeConfig {
+            interval: Duration::from_secs(1),
+            timeout: Duration::from_millis(100),
+            failure_threshold: 3,
+            recovery_threshold: 2,
+            degraded_latency: Duration::from_millis(50),
+        },
+        min_healthy_peers: 0,
+    }
+}
+
+/// Builds an instance whose store only holds `words`, without any peer.
+pub fn core(config: CoreConfig, words: &[&str]) -> FakeCore {
+    let service_id = config.service_id.clone();
+    let store = HashmapStore::from_records(
+        words
+            .iter()
+            .map(|word| WordRecord::new(word.to_string(), WordMetadata::default(), &service_id)),
+    );
+    Core::new(store, Arc::new(RwLock::new(Vec::new())), config)
+}
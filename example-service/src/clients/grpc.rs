@@ -1,61 +1,222 @@
 use std::time::Duration;
 
-use crate::clients::{Client, ClientError};
-use async_recursion::async_recursion;
+use crate::clients::{ChainEventStream, Client, ClientError, ConnectionState};
+use crate::core::{
+    ChainElement, ChainEvent, ChainMode, ChainResult, ChainState, FailurePolicy, Interruption,
+    Provenance,
+};
+use crate::interfaces::grpc::LIVENESS_SERVICE;
+use crate::membership::Member;
+use crate::strategies::ChainStrategyKind;
 use axum::http::uri::InvalidUri;
+use futures::StreamExt;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, Weak};
+use std::time::Instant;
 use thiserror::Error;
+use tokio::sync::OnceCell;
 use tonic::async_trait;
-use tonic::transport::Channel;
+use tonic::transport::{Channel, Endpoint};
+use tonic::Request;
+use tonic_health::pb::health_check_response::ServingStatus;
+use tonic_health::pb::health_client::HealthClient;
+use tonic_health::pb::HealthCheckRequest;
 use tonic_tracing_opentelemetry::middleware::client::{OtelGrpcLayer, OtelGrpcService};
 use tower::ServiceBuilder;
-use tracing::{trace, warn};
+use tracing::{debug, trace, warn, Instrument};
+use word::membership_service_client::MembershipServiceClient;
+use word::{chain_stream_response, NextWordRequest};
 use word::{word_service_client::WordServiceClient, ChainRequest, HealthRequest};
+use word::{GossipRequest, IndirectPingRequest};
 
-pub mod word {
-    tonic::include_proto!("word");
-}
+pub use crate::interfaces::grpc::word;
+
+/// Delay before the second connection attempt, doubled after each failure.
+const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Time a single connection attempt may take, as unreachable hosts may never refuse it.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
 
-const MAX_RETRIES: u8 = 10;
+type WordClient = WordServiceClient<OtelGrpcService<Channel>>;
+
+/// Clients of the services of the remote instance, sharing a single channel.
+#[derive(Clone, Debug)]
+struct Clients {
+    word: WordClient,
+    health: HealthClient<OtelGrpcService<Channel>>,
+}
 
 #[derive(Error, Debug)]
 pub enum GrpcClientError {
-    #[error("Failed to connect to the server")]
-    ConnectionError {
-        #[source]
-        source: tonic::transport::Error,
-        address: String,
-    },
     #[error("Invalid Uri when connecting to the server")]
     InvalidUri(#[source] InvalidUri),
 }
 
 #[derive(Clone, Debug)]
 pub struct GrpcClient {
-    client: WordServiceClient<OtelGrpcService<Channel>>,
+    /// Set once connected, shared between clones along with the connection attempts
+    client: Arc<OnceCell<Clients>>,
+    failed_attempts: Arc<AtomicU32>,
+    /// Cleared once the remote service turns out not to serve `grpc.health.v1.Health`, its
+    /// health being checked through the word service then
+    standard_health: Arc<AtomicBool>,
     service_url: String,
+    /// Shared between clones so that the remote service is only asked once
+    service_id: Arc<OnceCell<String>>,
 }
 
 impl GrpcClient {
-    pub async fn new(service_url: String) -> Result<Self, GrpcClientError> {
-        let client = connect_to_client(service_url.clone(), MAX_RETRIES).await?;
+    /// Creates a client connecting to the service at `service_url` in the background, requests
+    /// failing as unavailable until it is connected.
+    pub fn new(service_url: String) -> Result<Self, GrpcClientError> {
+        let endpoint = Channel::from_shared(service_url.clone())
+            .map_err(GrpcClientError::InvalidUri)?
+            .connect_timeout(CONNECT_TIMEOUT);
+        let client = Arc::new(OnceCell::new());
+        let failed_attempts = Arc::new(AtomicU32::new(0));
+
+        tokio::spawn(
+            connect_in_background(endpoint, Arc::downgrade(&client), failed_attempts.clone())
+                .in_current_span(),
+        );
 
         Ok(GrpcClient {
             client,
+            failed_attempts,
+            standard_health: Arc::new(AtomicBool::new(true)),
             service_url,
+            service_id: Arc::new(OnceCell::new()),
         })
     }
+
+    fn client(&self) -> Result<WordClient, ClientError<GrpcClientError>> {
+        self.client
+            .get()
+            .map(|clients| clients.word.clone())
+            .ok_or(ClientError::ServiceUnavailable)
+    }
+
+    fn health_client(
+        &self,
+    ) -> Result<HealthClient<OtelGrpcService<Channel>>, ClientError<GrpcClientError>> {
+        self.client
+            .get()
+            .map(|clients| clients.health.clone())
+            .ok_or(ClientError::ServiceUnavailable)
+    }
+}
+
+/// Client of the membership service of another instance, connecting on first use.
+#[derive(Clone, Debug)]
+pub struct GrpcMembershipClient {
+    client: MembershipServiceClient<Channel>,
+}
+
+impl GrpcMembershipClient {
+    pub fn new(service_url: String) -> Result<Self, GrpcClientError> {
+        // Not traced, members probing each other every protocol period
+        let channel = Channel::from_shared(service_url)
+            .map_err(GrpcClientError::InvalidUri)?
+            .connect_lazy();
+
+        Ok(GrpcMembershipClient {
+            client: MembershipServiceClient::new(channel),
+        })
+    }
+
+    /// Sends the view of the local instance, returning the one of the remote instance.
+    pub async fn gossip(
+        &mut self,
+        members: Vec<Member>,
+    ) -> Result<Vec<Member>, ClientError<GrpcClientError>> {
+        let response = self
+            .client
+            .gossip(GossipRequest {
+                members: members.into_iter().map(Member::into).collect(),
+            })
+            .await
+            .map_err(status_error)?
+            .into_inner();
+
+        Ok(response.members.into_iter().map(Member::from).collect())
+    }
+
+    /// Asks the remote instance to probe `url`, returning whether it answered along with the view
+    /// of the remote instance.
+    pub async fn indirect_ping(
+        &mut self,
+        url: String,
+        members: Vec<Member>,
+    ) -> Result<(bool, Vec<Member>), ClientError<GrpcClientError>> {
+        let response = self
+            .client
+            .indirect_ping(IndirectPingRequest {
+                url,
+                members: members.into_iter().map(Member::into).collect(),
+            })
+            .await
+            .map_err(status_error)?
+            .into_inner();
+
+        Ok((
+            response.acked,
+            response.members.into_iter().map(Member::from).collect(),
+        ))
+    }
 }
 
 #[async_trait]
 impl Client for GrpcClient {
     type E = GrpcClientError;
 
+    fn connect(url: String) -> Result<Self, ClientError<GrpcClientError>> {
+        GrpcClient::new(url).map_err(ClientError::_InternalClientError)
+    }
+
+    fn connection_state(&self) -> ConnectionState {
+        match self.client.initialized() {
+            true => ConnectionState::Connected,
+            false => ConnectionState::Connecting {
+                failed_attempts: self.failed_attempts.load(Ordering::Relaxed),
+            },
+        }
+    }
+
     fn get_url(&self) -> String {
         self.service_url.clone()
     }
 
     async fn health(&mut self) -> Result<(), ClientError<GrpcClientError>> {
-        self.client
+        if self.standard_health.load(Ordering::Relaxed) {
+            let request = HealthCheckRequest {
+                service: LIVENESS_SERVICE.to_string(),
+            };
+            match self.health_client()?.check(request).await {
+                Ok(response) => {
+                    return match response.into_inner().status() {
+                        ServingStatus::Serving => Ok(()),
+                        _ => Err(ClientError::ServiceUnavailable),
+                    };
+                }
+                Err(status)
+                    if matches!(
+                        status.code(),
+                        tonic::Code::Unimplemented | tonic::Code::NotFound
+                    ) =>
+                {
+                    debug!(
+                        "{0} does not report its liveness through grpc.health.v1, falling back to its health RPC",
+                        self.service_url
+                    );
+                    self.standard_health.store(false, Ordering::Relaxed);
+                }
+                Err(_) => return Err(ClientError::ServiceUnavailable),
+            }
+        }
+
+        self.client()?
             .health(HealthRequest {})
             .await
             .map_err(|_| ClientError::ServiceUnavailable)?;
@@ -63,59 +224,325 @@ impl Client for GrpcClient {
         Ok(())
     }
 
+    async fn service_id(&mut self) -> Result<String, ClientError<GrpcClientError>> {
+        let mut client = self.client()?;
+        self.service_id
+            .get_or_try_init(|| async move {
+                Ok(client
+                    .health(HealthRequest {})
+                    .await
+                    .map_err(|_| ClientError::ServiceUnavailable)?
+                    .into_inner()
+                    .service_id)
+            })
+            .await
+            .cloned()
+    }
+
     #[tracing::instrument(fields(component = "Grpc Client"), skip(self))]
     async fn chain(
         &mut self,
-        word_chain: Vec<String>,
-        count: u32,
-    ) -> Result<Vec<String>, ClientError<GrpcClientError>> {
-        let request = ChainRequest {
-            input: word_chain,
-            count,
-        };
+        state: ChainState,
+    ) -> Result<ChainResult, ClientError<GrpcClientError>> {
+        let deadline = state.deadline;
+        let request = chain_request(state);
         trace!("Sending chain request: {:?}", request.clone());
-        Ok(self
-            .client
-            .chain(request)
+        let response = self
+            .client()?
+            .chain(with_deadline(request, deadline))
+            .await
+            .map_err(status_error)?
+            .into_inner();
+
+        let interruption = interruption(response.interruption());
+        let policy = FailurePolicy::from(response.failure_policy());
+
+        // Services predating provenance only fill the output
+        let elements = if response.elements.is_empty() {
+            response
+                .output
+                .into_iter()
+                .map(|word| ChainElement {
+                    word,
+                    provenance: None,
+                })
+                .collect()
+        } else {
+            response.elements.into_iter().map(chain_element).collect()
+        };
+
+        Ok(ChainResult {
+            elements,
+            interruption,
+            error: Some(response.error).filter(|error| !error.is_empty()),
+            failure_policy: policy,
+            retries: response.retries,
+        })
+    }
+
+    #[tracing::instrument(fields(component = "Grpc Client"), skip(self))]
+    async fn chain_stream(
+        &mut self,
+        state: ChainState,
+    ) -> Result<ChainEventStream<GrpcClientError>, ClientError<GrpcClientError>> {
+        let deadline = state.deadline;
+        let request = chain_request(state);
+        trace!("Sending chain_stream request: {:?}", request.clone());
+        let responses = self
+            .client()?
+            .chain_stream(with_deadline(request, deadline))
             .await
-            .map_err(|status| match status.code() {
-                tonic::Code::InvalidArgument => {
-                    ClientError::BadRequest(status.message().to_string())
+            .map_err(status_error)?
+            .into_inner();
+
+        Ok(responses
+            .filter_map(|response| async move {
+                match response.map(|response| response.event) {
+                    Ok(Some(chain_stream_response::Event::Element(element))) => {
+                        Some(Ok(ChainEvent::Element(chain_element(element))))
+                    }
+                    Ok(Some(chain_stream_response::Event::Interrupted(interrupted))) => {
+                        interruption(interrupted.interruption()).map(|interruption| {
+                            Ok(ChainEvent::Interrupted {
+                                interruption,
+                                error: Some(interrupted.error).filter(|error| !error.is_empty()),
+                            })
+                        })
+                    }
+                    Ok(None) => None,
+                    Err(status) => Some(Err(status_error(status))),
                 }
-                _ => ClientError::InternalServerError,
-            })?
+            })
+            .boxed())
+    }
+
+    #[tracing::instrument(fields(component = "Grpc Client"), skip(self))]
+    async fn next_word(
+        &mut self,
+        state: ChainState,
+    ) -> Result<Option<String>, ClientError<GrpcClientError>> {
+        let deadline = state.deadline;
+        let request = next_word_request(state);
+        trace!("Sending next_word request: {:?}", request.clone());
+        Ok(self
+            .client()?
+            .next_word(with_deadline(request, deadline))
+            .await
+            .map_err(status_error)?
             .into_inner()
-            .output)
+            .word)
     }
 }
 
-#[async_recursion]
-async fn connect_to_client(
-    service_url: String,
-    retries: u8,
-) -> Result<WordServiceClient<OtelGrpcService<Channel>>, GrpcClientError> {
-    match Channel::from_shared(service_url.clone())
-        .map_err(GrpcClientError::InvalidUri)?
-        .connect()
-        .await
-    {
-        Ok(channel) => Ok(WordServiceClient::new(
-            ServiceBuilder::new().layer(OtelGrpcLayer).service(channel),
-        )),
-        Err(e) => {
-            if retries > 0 {
+fn chain_request(state: ChainState) -> ChainRequest {
+    let mode = match state.options.mode {
+        ChainMode::Recursive => word::ChainMode::Recursive,
+        ChainMode::Orchestrated => word::ChainMode::Orchestrated,
+    };
+    ChainRequest {
+        input: state.chain,
+        count: state.count,
+        strategy: state
+            .options
+            .strategy
+            .map(|kind| chain_strategy(kind).into()),
+        visited_services: state.visited_services,
+        trace: state.trace.into_iter().map(word::Hop::from).collect(),
+        avoid_revisiting_services: state.options.avoid_revisiting_services,
+        unique_words: state.options.unique_words,
+        timeout_ms: None,
+        allow_partial: state.options.allow_partial,
+        mode: mode.into(),
+        failure_policy: state
+            .options
+            .failure_policy
+            .map(|policy| word::FailurePolicy::from(policy).into()),
+        retries: state.retries,
+    }
+}
+
+/// Sends the trace along with the chain, so that the words of earlier hops are not taken for input.
+fn next_word_request(state: ChainState) -> NextWordRequest {
+    NextWordRequest {
+        input: state.chain,
+        strategy: state
+            .options
+            .strategy
+            .map(|kind| chain_strategy(kind).into()),
+        unique_words: state.options.unique_words,
+        visited_services: state.visited_services,
+        trace: state.trace.into_iter().map(word::Hop::from).collect(),
+    }
+}
+
+fn chain_element(element: word::ChainElement) -> ChainElement {
+    ChainElement {
+        word: element.word,
+        provenance: element.provenance.map(|provenance| Provenance {
+            service_id: provenance.service_id,
+            hop_index: provenance.hop_index,
+            latency: Duration::from_micros(provenance.latency_us),
+        }),
+    }
+}
+
+fn interruption(interruption: word::ChainInterruption) -> Option<Interruption> {
+    match interruption {
+        word::ChainInterruption::None => None,
+        word::ChainInterruption::DeadlineExceeded => Some(Interruption::DeadlineExceeded),
+        word::ChainInterruption::PeerFailed => Some(Interruption::PeerFailed),
+    }
+}
+
+fn chain_strategy(kind: ChainStrategyKind) -> word::ChainStrategy {
+    match kind {
+        ChainStrategyKind::Random => word::ChainStrategy::Random,
+        ChainStrategyKind::NoRepeat => word::ChainStrategy::NoRepeat,
+        ChainStrategyKind::Markov => word::ChainStrategy::Markov,
+        ChainStrategyKind::RoundRobin => word::ChainStrategy::RoundRobin,
+    }
+}
+
+fn with_deadline<T>(message: T, deadline: Option<Instant>) -> Request<T> {
+    let mut request = Request::new(message);
+    if let Some(deadline) = deadline {
+        // Sent as the grpc-timeout header, so the remote service knows how long it has left
+        request.set_timeout(deadline.saturating_duration_since(Instant::now()));
+    }
+    request
+}
+
+fn status_error(status: tonic::Status) -> ClientError<GrpcClientError> {
+    match status.code() {
+        tonic::Code::DeadlineExceeded | tonic::Code::Cancelled => ClientError::DeadlineExceeded,
+        tonic::Code::InvalidArgument => ClientError::BadRequest(status.message().to_string()),
+        tonic::Code::FailedPrecondition => {
+            ClientError::FailedPrecondition(status.message().to_string())
+        }
+        tonic::Code::Aborted => ClientError::Aborted(status.message().to_string()),
+        tonic::Code::Unavailable => ClientError::ServiceUnavailable,
+        _ => ClientError::InternalServerError,
+    }
+}
+
+/// Connects to `endpoint` until it succeeds, or until every clone of the client is dropped.
+async fn connect_in_background(
+    endpoint: Endpoint,
+    client: Weak<OnceCell<Clients>>,
+    failed_attempts: Arc<AtomicU32>,
+) {
+    loop {
+        match endpoint.connect().await {
+            Ok(channel) => {
+                if let Some(client) = client.upgrade() {
+                    let service = ServiceBuilder::new().layer(OtelGrpcLayer).service(channel);
+                    let _ = client.set(Clients {
+                        word: WordServiceClient::new(service.clone()),
+                        health: HealthClient::new(service),
+                    });
+                    debug!("Connected gRPC client to {:?}", endpoint.uri());
+                }
+                return;
+            }
+            Err(e) => {
+                let attempts = failed_attempts.fetch_add(1, Ordering::Relaxed) + 1;
+                let delay = backoff(attempts);
                 warn!(
-                    "Failed to connect to server {0} : {1}. Retrying...",
-                    service_url, e
+                    "Failed to connect to server {0} : {1}. Retrying in {2:?}...",
+                    endpoint.uri(),
+                    e,
+                    delay
                 );
-                tokio::time::sleep(Duration::from_secs(5)).await;
-                connect_to_client(service_url, retries - 1).await
-            } else {
-                Err(GrpcClientError::ConnectionError {
-                    source: e,
-                    address: service_url.clone(),
-                })
+                tokio::time::sleep(delay).await;
+                if client.strong_count() == 0 {
+                    debug!(
+                        "Client of {:?} dropped, no longer connecting",
+                        endpoint.uri()
+                    );
+                    return;
+                }
             }
         }
     }
 }
+
+/// Exponential backoff with equal jitter: half of the delay is random, so that
+/// instances failing to connect at the same time do not retry in lockstep.
+fn backoff(failed_attempts: u32) -> Duration {
+    let delay = INITIAL_BACKOFF
+        .saturating_mul(2_u32.saturating_pow(failed_attempts.saturating_sub(1)))
+        .min(MAX_BACKOFF);
+    delay / 2 + delay.mul_f64(rand::random::<f64>() / 2.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clients::fake;
+    use crate::core::Hop;
+    use crate::interfaces::grpc::word::word_service_server::WordService;
+    use crate::interfaces::grpc::GrpcInterface;
+
+    /// Range of the delays of `samples` backoffs after `failed_attempts` failures.
+    fn backoff_range(failed_attempts: u32, samples: usize) -> (Duration, Duration) {
+        let delays: Vec<Duration> = (0..samples).map(|_| backoff(failed_attempts)).collect();
+        (*delays.iter().min().unwrap(), *delays.iter().max().unwrap())
+    }
+
+    #[test]
+    fn backoff_doubles_with_each_failure() {
+        for failed_attempts in 1..=6 {
+            let delay = INITIAL_BACKOFF * 2_u32.pow(failed_attempts - 1);
+            let (min, max) = backoff_range(failed_attempts, 200);
+            assert!(min >= delay / 2, "{failed_attempts} failures: {min:?}");
+            assert!(max <= delay, "{failed_attempts} failures: {max:?}");
+        }
+    }
+
+    #[test]
+    fn backoff_is_capped() {
+        for failed_attempts in [7, 20, 64, u32::MAX] {
+            let (min, max) = backoff_range(failed_attempts, 200);
+            assert!(
+                min >= MAX_BACKOFF / 2,
+                "{failed_attempts} failures: {min:?}"
+            );
+            assert!(max <= MAX_BACKOFF, "{failed_attempts} failures: {max:?}");
+        }
+    }
+
+    #[test]
+    fn backoff_is_jittered() {
+        let (min, max) = backoff_range(3, 200);
+        // Equal jitter spreads delays over the upper half of the range
+        let delay = INITIAL_BACKOFF * 4;
+        assert!(min < delay * 5 / 8, "{min:?}");
+        assert!(max > delay * 7 / 8, "{max:?}");
+    }
+
+    #[tokio::test]
+    async fn next_word_requests_keep_earlier_hops_out_of_the_input() {
+        let mut config = fake::config("c");
+        config.limits.max_input_length = 1;
+        let interface = GrpcInterface::new(fake::core(config, &["gamma"]));
+        let hop = |service_id: &str, word: &str| Hop {
+            service_id: service_id.to_string(),
+            word: word.to_string(),
+            latency: Duration::ZERO,
+        };
+        // Orchestrated chain whose input and hops go past the input length
+        let state = ChainState {
+            chain: ["start", "alpha", "beta"].map(str::to_string).to_vec(),
+            count: 3,
+            visited_services: vec!["a".to_string(), "b".to_string()],
+            trace: vec![hop("a", "alpha"), hop("b", "beta")],
+            ..Default::default()
+        };
+
+        let response = interface
+            .next_word(Request::new(next_word_request(state)))
+            .await
+            .unwrap();
+        assert_eq!(response.into_inner().word, Some("gamma".to_string()));
+    }
+}
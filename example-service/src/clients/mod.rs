@@ -1,7 +1,12 @@
+use crate::core::{ChainEvent, ChainResult, ChainState};
+use futures::stream::BoxStream;
+use serde::Serialize;
 use std::{error::Error, fmt::Debug};
 use thiserror::Error;
 use tonic::async_trait;
 
+#[cfg(test)]
+pub mod fake;
 pub mod grpc;
 
 #[derive(Error, Debug)]
@@ -10,23 +15,58 @@ pub enum ClientError<E: Error> {
     BadRequest(String),
     #[error("Service unavailable")]
     ServiceUnavailable,
+    #[error("Failed precondition: {0}")]
+    FailedPrecondition(String),
+    #[error("Deadline exceeded")]
+    DeadlineExceeded,
+    #[error("Aborted: {0}")]
+    Aborted(String),
     #[error("Internal client error: {0}")]
     _InternalClientError(#[source] E),
     #[error("Internal server error")]
     InternalServerError,
 }
 
+/// Whether a client may send requests to its remote service.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ConnectionState {
+    /// Still connecting in the background, requests failing as unavailable meanwhile
+    Connecting {
+        failed_attempts: u32,
+    },
+    Connected,
+}
+
+pub type ChainEventStream<E> = BoxStream<'static, Result<ChainEvent, ClientError<E>>>;
+
 #[async_trait]
 pub trait Client: Clone + Send + Sync + 'static + Debug {
-    type E: Error;
+    type E: Error + Send + Sync + 'static;
+
+    /// Creates a client of the service at `url`, which keeps connecting to it in the background.
+    fn connect(url: String) -> Result<Self, ClientError<Self::E>>;
+
+    fn connection_state(&self) -> ConnectionState;
 
     fn get_url(&self) -> String;
 
     async fn health(&mut self) -> Result<(), ClientError<Self::E>>;
 
-    async fn chain(
+    /// Identifier of the remote service, as reported by its health endpoint.
+    async fn service_id(&mut self) -> Result<String, ClientError<Self::E>>;
+
+    async fn chain(&mut self, state: ChainState) -> Result<ChainResult, ClientError<Self::E>>;
+
+    /// Sends the chain on to the remote service, which emits each word as soon as it is picked.
+    async fn chain_stream(
+        &mut self,
+        state: ChainState,
+    ) -> Result<ChainEventStream<Self::E>, ClientError<Self::E>>;
+
+    /// Asks the remote service for the word following the chain, without forwarding it any further.
+    async fn next_word(
         &mut self,
-        word_chain: Vec<String>,
-        count: u32,
-    ) -> Result<Vec<String>, ClientError<Self::E>>;
+        state: ChainState,
+    ) -> Result<Option<String>, ClientError<Self::E>>;
 }
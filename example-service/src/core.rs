@@ -1,12 +1,22 @@
-use crate::clients::{Client, ClientError};
-use crate::stores::{Store, StoreError};
-use rand::random_range;
+use crate::clients::{Client, ClientError, ConnectionState};
+use crate::stores::{
+    is_valid_weight, AddWordStatus, Store, StoreError, WordMetadata, WordPage, WordRecord,
+};
+use crate::strategies::{builtin_strategies, ChainStrategy, ChainStrategyKind, Strategies};
+use futures::future::join_all;
+use futures::stream::{BoxStream, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 use std::error::Error;
 use std::fmt::Debug;
+use std::future::Future;
 use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use thiserror::Error;
-use tokio::sync::RwLock;
-use tracing::{debug, error, info, warn};
+use tokio::sync::{mpsc, RwLock};
+use tokio::time::MissedTickBehavior;
+use tokio_stream::wrappers::ReceiverStream;
+use tracing::{debug, error, info, warn, Instrument};
 
 #[derive(Error, Debug)]
 pub enum CoreError<SE: Error, CE: Error> {
@@ -26,44 +36,834 @@ pub enum CoreError<SE: Error, CE: Error> {
     IndexError,
     #[error("This service is not connected to another example-service")]
     NoConnectedServices,
+    #[error("Invalid argument: {0}")]
+    InvalidArgument(String),
+    #[error("Chain cannot be continued: {0}")]
+    UnsatisfiableChain(String),
+    #[error("Chain deadline exceeded")]
+    DeadlineExceeded,
+    #[error("A hop of the chain failed: {0}")]
+    HopFailed(String),
+    #[error("Peer {0} not found")]
+    PeerNotFound(String),
+    #[error("Peer {0} is already connected")]
+    PeerAlreadyConnected(String),
+}
+
+/// Maximum number of words returned by a single `list_words` call.
+pub const MAX_LIST_LIMIT: usize = 1000;
+
+/// Number of words drawn from a strategy before giving up on finding one that is not part of the chain.
+const MAX_UNIQUE_WORD_ATTEMPTS: usize = 16;
+
+/// Number of chain events buffered while the receiver of a chain stream is busy.
+const CHAIN_STREAM_BUFFER: usize = 16;
+
+/// Time kept by each hop to send its response back, subtracted from the deadline given to the next hop.
+const DEADLINE_MARGIN: Duration = Duration::from_millis(20);
+
+/// How the words of a chain are gathered from the connected services.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ChainMode {
+    /// Each service appends its word and forwards the chain to one of its peers
+    #[default]
+    Recursive,
+    /// The service receiving the request asks its peers for one word at a time,
+    /// so no request stays open while the rest of the chain is built
+    Orchestrated,
+}
+
+/// What a service does when the peer it sends the chain to fails.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FailurePolicy {
+    /// Fail the whole chain
+    #[default]
+    FailFast,
+    /// Send the chain to another eligible peer, up to the configured number of retries, unless
+    /// a streamed chain already relayed words of the failed peer
+    RetryOtherPeer,
+    /// Return the words gathered so far, annotated with the error
+    Partial,
+}
+
+/// Options of a chain request, applied by every service the chain goes through.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChainOptions {
+    /// Strategy picked by the service receiving the request when unset
+    #[serde(default)]
+    pub strategy: Option<ChainStrategyKind>,
+    /// Never forward the chain to a service it already went through
+    #[serde(default)]
+    pub avoid_revisiting_services: bool,
+    /// Never append a word that is already part of the chain
+    #[serde(default)]
+    pub unique_words: bool,
+    /// Return the words gathered so far instead of failing when the deadline is exceeded
+    #[serde(default)]
+    pub allow_partial: bool,
+    #[serde(default)]
+    pub mode: ChainMode,
+    /// Policy of the service receiving the request when unset
+    #[serde(default)]
+    pub failure_policy: Option<FailurePolicy>,
+}
+
+/// A service the chain went through and the word it appended.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hop {
+    pub service_id: String,
+    pub word: String,
+    /// Time the service took to pick its word
+    pub latency: Duration,
+}
+
+/// Where a word of a chain comes from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Provenance {
+    pub service_id: String,
+    /// Position of the hop in the chain, starting at 0 for the first service
+    pub hop_index: u32,
+    pub latency: Duration,
+}
+
+/// Why a chain stopped before reaching the requested count.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Interruption {
+    DeadlineExceeded,
+    PeerFailed,
+}
+
+/// The words of a chain, `interruption` being set when it was cut short.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChainResult {
+    pub elements: Vec<ChainElement>,
+    pub interruption: Option<Interruption>,
+    /// Error that cut the chain short, when a peer failed
+    pub error: Option<String>,
+    pub failure_policy: FailurePolicy,
+    /// Number of hops sent again to another peer after a failure
+    pub retries: u32,
+}
+
+/// A word of a chain, along with its provenance unless it was part of the input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChainElement {
+    pub word: String,
+    pub provenance: Option<Provenance>,
+}
+
+/// A chain travelling between services, along with the hops it went through so far.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChainState {
+    pub chain: Vec<String>,
+    /// Number of words to add after the one of the service receiving the chain
+    pub count: u32,
+    pub options: ChainOptions,
+    /// Identifiers of the services the chain went through
+    pub visited_services: Vec<String>,
+    pub trace: Vec<Hop>,
+    /// Point in time by which the response must be sent back, the chain being
+    /// extended until [`DEADLINE_MARGIN`] before it
+    pub deadline: Option<Instant>,
+    /// Number of hops sent again to another peer after a failure so far
+    pub retries: u32,
+}
+
+impl ChainState {
+    fn is_expired(&self) -> bool {
+        self.deadline
+            .is_some_and(|deadline| Instant::now() + DEADLINE_MARGIN >= deadline)
+    }
+
+    fn complete(self) -> ChainResult {
+        self.into_result(None, None)
+    }
+
+    fn into_result(self, interruption: Option<Interruption>, error: Option<String>) -> ChainResult {
+        ChainResult {
+            failure_policy: self.options.failure_policy.unwrap_or_default(),
+            retries: self.retries,
+            elements: self.into_elements(),
+            interruption,
+            error,
+        }
+    }
+
+    /// Ends the chain early because of `err`, with the words gathered so far
+    /// when the options and failure policy allow it.
+    fn interrupt<SE: Error, CE: Error>(
+        self,
+        err: CoreError<SE, CE>,
+    ) -> Result<ChainResult, CoreError<SE, CE>> {
+        warn!("Chain interrupted: {}", err);
+        let policy = self.options.failure_policy.unwrap_or_default();
+        match err {
+            CoreError::DeadlineExceeded if self.options.allow_partial => {
+                Ok(self.into_result(Some(Interruption::DeadlineExceeded), None))
+            }
+            CoreError::ClientError(err) if policy == FailurePolicy::Partial => {
+                Ok(self.into_result(Some(Interruption::PeerFailed), Some(err.to_string())))
+            }
+            CoreError::HopFailed(msg) if policy == FailurePolicy::Partial => {
+                Ok(self.into_result(Some(Interruption::PeerFailed), Some(msg)))
+            }
+            err => Err(err),
+        }
+    }
+
+    /// Lists the words of the chain, the last ones being attributed to the hops of the trace.
+    pub fn into_elements(self) -> Vec<ChainElement> {
+        let input_length = self.chain.len().saturating_sub(self.trace.len());
+        let mut hops = self.trace.into_iter().enumerate();
+        self.chain
+            .into_iter()
+            .enumerate()
+            .map(|(position, word)| {
+                let provenance = match position < input_length {
+                    true => None,
+                    false => hops.next().map(|(hop_index, hop)| Provenance {
+                        service_id: hop.service_id,
+                        hop_index: hop_index as u32,
+                        latency: hop.latency,
+                    }),
+                };
+                ChainElement { word, provenance }
+            })
+            .collect()
+    }
+}
+
+pub type WordStream<SE, CE> = BoxStream<'static, Result<WordRecord, CoreError<SE, CE>>>;
+
+/// What a chain stream emits, each word as soon as it is picked and then why
+/// the chain was cut short, if it was.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChainEvent {
+    Element(ChainElement),
+    Interrupted {
+        interruption: Interruption,
+        error: Option<String>,
+    },
+}
+
+pub type ChainEventStream<SE, CE> = BoxStream<'static, Result<ChainEvent, CoreError<SE, CE>>>;
+
+type ChainEventSender<SE, CE> = mpsc::Sender<Result<ChainEvent, CoreError<SE, CE>>>;
+
+/// Outcome of appending the word of a service to a chain.
+enum Extension<S: Store, C: Client> {
+    Done(ChainResult),
+    /// The chain goes on, using the strategy resolved by this service
+    Forward(ChainState, Arc<dyn ChainStrategy<S, C>>),
+}
+
+/// Bounds on chain requests, keeping a single call from fanning out across the cluster.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ChainLimits {
+    /// Highest number of words a chain request may ask for
+    pub max_chain_count: u32,
+    /// Highest number of words a chain request may start from
+    pub max_input_length: usize,
+    /// Highest number of characters of a word given as input
+    pub max_word_length: usize,
+}
+
+/// State of a peer, as maintained by the background health prober.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PeerState {
+    /// Still connecting in the background, chains skipping it meanwhile
+    Connecting,
+    Healthy,
+    /// Failed its last probes or answered them slowly, still eligible for chains
+    Degraded,
+    /// Failed too many probes in a row, chains skipping it until it recovers
+    OpenCircuit,
+}
+
+/// Thresholds of the background health prober.
+#[derive(Clone, Debug)]
+pub struct ProbeConfig {
+    /// Interval between two probes of a peer
+    pub interval: Duration,
+    /// Time a peer has to answer a probe
+    pub timeout: Duration,
+    /// Consecutive failed probes opening the circuit of a peer
+    pub failure_threshold: u32,
+    /// Consecutive successful probes closing the circuit of a peer
+    pub recovery_threshold: u32,
+    /// Probe latency above which a peer is degraded
+    pub degraded_latency: Duration,
+}
+
+/// Health of a connected service, updated by each of its probes.
+#[derive(Debug, Clone)]
+struct PeerStatus {
+    state: PeerState,
+    consecutive_failures: u32,
+    consecutive_successes: u32,
+    last_health: Option<PeerHealth>,
+}
+
+impl Default for PeerStatus {
+    fn default() -> Self {
+        PeerStatus {
+            state: PeerState::Healthy,
+            consecutive_failures: 0,
+            consecutive_successes: 0,
+            last_health: None,
+        }
+    }
+}
+
+impl PeerStatus {
+    /// Records a probe, returning the state the peer was in before it.
+    fn record(&mut self, health: PeerHealth, config: &ProbeConfig) -> PeerState {
+        let previous_state = self.state;
+        if health.healthy {
+            self.consecutive_failures = 0;
+            self.consecutive_successes += 1;
+            self.state = match self.state {
+                PeerState::OpenCircuit
+                    if self.consecutive_successes < config.recovery_threshold =>
+                {
+                    PeerState::OpenCircuit
+                }
+                _ if health.latency > config.degraded_latency => PeerState::Degraded,
+                _ => PeerState::Healthy,
+            };
+        } else {
+            self.consecutive_successes = 0;
+            self.consecutive_failures += 1;
+            // A peer failing again while recovering does not get to count its failures anew
+            self.state = if self.consecutive_failures >= config.failure_threshold
+                || self.state == PeerState::OpenCircuit
+            {
+                PeerState::OpenCircuit
+            } else {
+                PeerState::Degraded
+            };
+        }
+        self.last_health = Some(health);
+        previous_state
+    }
+}
+
+/// Outcome of the last health check of a peer.
+#[derive(Debug, Clone)]
+pub struct PeerHealth {
+    pub healthy: bool,
+    pub error: Option<String>,
+    pub latency: Duration,
+    /// Time of the check in milliseconds since the Unix epoch
+    pub checked_at: u64,
+}
+
+/// A service chains may be sent to.
+#[derive(Debug, Clone)]
+pub struct Peer {
+    /// Unknown until the peer first answers a health check
+    pub id: Option<String>,
+    pub url: String,
+    pub state: PeerState,
+    /// Failed attempts to connect to the peer, while it is connecting
+    pub failed_connection_attempts: u32,
+    /// Failed probes in a row, while it is connected
+    pub consecutive_failures: u32,
+    pub last_health: Option<PeerHealth>,
+}
+
+/// Outcome of checking that the store answers.
+#[derive(Debug, Clone)]
+pub struct StoreHealth {
+    pub healthy: bool,
+    pub error: Option<String>,
+    pub latency: Duration,
+}
+
+/// Health of each component the readiness of the service depends on.
+#[derive(Debug, Clone)]
+pub struct HealthDetails {
+    pub ready: bool,
+    pub store: StoreHealth,
+    /// Number of peers chains may go to, that is healthy or degraded ones
+    pub healthy_peers: usize,
+    pub min_healthy_peers: usize,
+    pub peers: Vec<Peer>,
+}
+
+#[derive(Clone, Debug)]
+pub struct CoreConfig {
+    /// Identifier of this instance, recorded as the origin of the words it creates
+    pub service_id: String,
+    /// Strategy used by chain requests that do not pick one
+    pub chain_strategy: ChainStrategyKind,
+    pub limits: ChainLimits,
+    /// Failure policy used by chain requests that do not pick one
+    pub failure_policy: FailurePolicy,
+    /// Number of other peers a hop is sent to after a failure, with the retry_other_peer policy
+    pub max_hop_retries: u32,
+    pub probe: ProbeConfig,
+    /// Number of healthy or degraded peers below which the service is not ready
+    pub min_healthy_peers: usize,
+}
+
+/// Peers changed at runtime through the API, which discovery leaves as they are.
+#[derive(Debug, Clone, Default)]
+pub struct ManualPeers {
+    /// Urls kept connected even when discovery no longer finds them
+    pub added: HashSet<String>,
+    /// Urls not connected to again even when discovery finds them
+    pub removed: HashSet<String>,
 }
 
 #[derive(Clone, Debug)]
 pub struct Core<S: Store, C: Client> {
     store: S,
     connected_services: Arc<RwLock<Vec<C>>>,
+    /// Health of each connected service, by url
+    peer_status: Arc<RwLock<HashMap<String, PeerStatus>>>,
+    manual_peers: Arc<RwLock<ManualPeers>>,
+    config: Arc<CoreConfig>,
+    strategies: Arc<Strategies<S, C>>,
+}
+
+/// Words are exported one per line, so they cannot hold line breaks themselves.
+pub fn check_word<SE: Error, CE: Error>(word: &str) -> Result<(), CoreError<SE, CE>> {
+    match word.contains(['\n', '\r']) {
+        true => Err(CoreError::InvalidArgument(format!(
+            "Word must not contain line breaks, got {:?}",
+            word
+        ))),
+        false => Ok(()),
+    }
+}
+
+pub fn check_weight<SE: Error, CE: Error>(weight: Option<f64>) -> Result<(), CoreError<SE, CE>> {
+    match weight {
+        Some(weight) if !is_valid_weight(weight) => Err(CoreError::InvalidArgument(format!(
+            "Weight must be a finite, non-negative number, got {}",
+            weight
+        ))),
+        _ => Ok(()),
+    }
+}
+
+fn check_limits<SE: Error, CE: Error>(
+    limits: &ChainLimits,
+    state: &ChainState,
+) -> Result<(), CoreError<SE, CE>> {
+    if state.count > limits.max_chain_count {
+        return Err(CoreError::InvalidArgument(format!(
+            "Count must be at most {}, got {}",
+            limits.max_chain_count, state.count
+        )));
+    }
+
+    // A chain reaches a service after at most `max_chain_count` hops, the words they added being
+    // the last ones of the chain rather than part of the input
+    if state.trace.len() > limits.max_chain_count as usize {
+        return Err(CoreError::InvalidArgument(format!(
+            "Trace must have at most {} hops, got {}",
+            limits.max_chain_count,
+            state.trace.len()
+        )));
+    }
+    let input_length = state.chain.len().saturating_sub(state.trace.len());
+    let hop_words = state.trace.iter().map(|hop| &hop.word);
+    if !hop_words.eq(state.chain[input_length..].iter()) {
+        return Err(CoreError::InvalidArgument(
+            "Trace must match the last words of the chain".to_string(),
+        ));
+    }
+    if input_length > limits.max_input_length {
+        return Err(CoreError::InvalidArgument(format!(
+            "Input must have at most {} words, got {}",
+            limits.max_input_length, input_length
+        )));
+    }
+
+    match state.chain[..input_length]
+        .iter()
+        .find(|word| word.chars().count() > limits.max_word_length)
+    {
+        Some(word) => Err(CoreError::InvalidArgument(format!(
+            "Words must have at most {} characters, got '{}'",
+            limits.max_word_length, word
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Tells whether another peer may succeed where `err` occurred.
+fn is_retryable<E: Error>(err: &ClientError<E>) -> bool {
+    !matches!(
+        err,
+        ClientError::DeadlineExceeded
+            // Another peer would reject the same request
+            | ClientError::BadRequest(_)
+            | ClientError::FailedPrecondition(_)
+            // A later hop already applied the failure policy, retrying would redo its work
+            | ClientError::Aborted(_)
+    )
+}
+
+fn peer_error<SE: Error, CE: Error>(err: ClientError<CE>) -> CoreError<SE, CE> {
+    match err {
+        ClientError::DeadlineExceeded => CoreError::DeadlineExceeded,
+        ClientError::FailedPrecondition(msg) => CoreError::UnsatisfiableChain(msg),
+        ClientError::Aborted(msg) => CoreError::HopFailed(msg),
+        err => CoreError::ClientError(err),
+    }
+}
+
+/// Sends `event` to the receiver of a chain stream, returning `false` once it is gone.
+async fn send_event<SE: Error, CE: Error>(
+    sender: &ChainEventSender<SE, CE>,
+    event: ChainEvent,
+) -> bool {
+    if sender.send(Ok(event)).await.is_err() {
+        debug!("Chain stream receiver dropped, stopping the chain");
+        return false;
+    }
+    true
+}
+
+async fn send_interruption<SE: Error, CE: Error>(
+    sender: &ChainEventSender<SE, CE>,
+    result: ChainResult,
+) {
+    if let Some(interruption) = result.interruption {
+        send_event(
+            sender,
+            ChainEvent::Interrupted {
+                interruption,
+                error: result.error,
+            },
+        )
+        .await;
+    }
 }
 
 impl<S: Store, C: Client> Core<S, C> {
-    pub fn new(store: S, connected_services: Arc<RwLock<Vec<C>>>) -> Self {
+    pub fn new(store: S, connected_services: Arc<RwLock<Vec<C>>>, config: CoreConfig) -> Self {
         Core {
             store,
             connected_services,
+            peer_status: Arc::new(RwLock::new(HashMap::new())),
+            manual_peers: Arc::new(RwLock::new(ManualPeers::default())),
+            config: Arc::new(config),
+            strategies: Arc::new(builtin_strategies()),
         }
     }
 
-    pub async fn health_check(&self) -> Result<(), CoreError<S::E, C::E>> {
-        let mut connected_services = self.connected_services.read().await.clone();
+    pub fn service_id(&self) -> &str {
+        &self.config.service_id
+    }
 
-        for service in connected_services.iter_mut() {
-            if let Err(e) = service.health().await {
-                error!(
-                    "Health check failed for connected service {:0}: {:?}",
-                    service.get_url(),
-                    e
+    pub fn limits(&self) -> &ChainLimits {
+        &self.config.limits
+    }
+
+    /// Urls of the services chains may be sent to.
+    pub async fn connected_urls(&self) -> Vec<String> {
+        self.connected_services
+            .read()
+            .await
+            .iter()
+            .map(|service| service.get_url())
+            .collect()
+    }
+
+    /// Adds a connected service, unless a service with the same url is already connected.
+    pub async fn add_service(&self, service: C) -> bool {
+        let mut connected_services = self.connected_services.write().await;
+        if connected_services
+            .iter()
+            .any(|connected| connected.get_url() == service.get_url())
+        {
+            return false;
+        }
+        connected_services.push(service);
+        true
+    }
+
+    /// Drops the connected service at `url`, returning whether there was one.
+    pub async fn remove_service(&self, url: &str) -> bool {
+        let mut connected_services = self.connected_services.write().await;
+        let num_connected_services = connected_services.len();
+        connected_services.retain(|service| service.get_url() != url);
+        self.peer_status.write().await.remove(url);
+        connected_services.len() != num_connected_services
+    }
+
+    /// Probes `service`, recording the outcome in its health state.
+    async fn probe_peer(&self, mut service: C) -> PeerState {
+        let url = service.get_url();
+        let start = Instant::now();
+        let result = match tokio::time::timeout(self.config.probe.timeout, service.health()).await {
+            Ok(result) => result,
+            Err(_) => Err(ClientError::DeadlineExceeded),
+        };
+        let latency = start.elapsed();
+        let health = PeerHealth {
+            healthy: result.is_ok(),
+            error: result.err().map(|e| e.to_string()),
+            latency,
+            checked_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|duration| duration.as_millis() as u64)
+                .unwrap_or_default(),
+        };
+
+        let mut peer_status = self.peer_status.write().await;
+        let status = peer_status.entry(url.clone()).or_default();
+        let previous_state = status.record(health, &self.config.probe);
+        if status.state != previous_state {
+            warn!(
+                component = "Core",
+                peer = url,
+                monotonic_counter.peer_state_changes = 1_u64,
+                "Peer {0} is now {1:?}, was {2:?}",
+                url,
+                status.state,
+                previous_state
+            );
+        }
+        info!(
+            component = "Core",
+            peer = url,
+            histogram.peer_probe_latency_us = latency.as_micros() as u64,
+            "Probed peer {0}",
+            url
+        );
+        status.state
+    }
+
+    /// Probes the connected services at the configured interval, keeping their health state up to date.
+    pub async fn run_prober(self) {
+        let mut ticker = tokio::time::interval(self.config.probe.interval);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        let mut last_counts = None;
+        loop {
+            ticker.tick().await;
+            let connected_services = self.connected_services.read().await.clone();
+            let states = join_all(
+                connected_services
+                    .into_iter()
+                    .filter(|service| service.connection_state() == ConnectionState::Connected)
+                    .map(|service| self.probe_peer(service)),
+            )
+            .await;
+
+            let count = |state| states.iter().filter(|s| **s == state).count() as u64;
+            let counts = (
+                count(PeerState::Healthy),
+                count(PeerState::Degraded),
+                count(PeerState::OpenCircuit),
+            );
+            if last_counts != Some(counts) {
+                info!(
+                    component = "Core",
+                    gauge.healthy_peers = counts.0,
+                    gauge.degraded_peers = counts.1,
+                    gauge.open_circuit_peers = counts.2,
+                    "{0} healthy, {1} degraded and {2} open-circuit peers",
+                    counts.0,
+                    counts.1,
+                    counts.2
                 );
-                return Err(CoreError::ServiceUnavailable);
+                last_counts = Some(counts);
             }
         }
+    }
+
+    /// State of `service`, connected services being healthy until probed otherwise.
+    async fn peer_state(&self, service: &C) -> PeerState {
+        match service.connection_state() {
+            ConnectionState::Connecting { .. } => PeerState::Connecting,
+            ConnectionState::Connected => self
+                .peer_status
+                .read()
+                .await
+                .get(&service.get_url())
+                .map_or(PeerState::Healthy, |status| status.state),
+        }
+    }
+
+    /// Asks `service` for its identifier, giving up after the probe timeout so that a peer that
+    /// stopped answering does not hold up the caller.
+    async fn identify(&self, service: &mut C) -> Result<String, ClientError<C::E>> {
+        tokio::time::timeout(self.config.probe.timeout, service.service_id())
+            .await
+            .unwrap_or(Err(ClientError::DeadlineExceeded))
+    }
+
+    async fn peer(&self, mut service: C) -> Peer {
+        let failed_connection_attempts = match service.connection_state() {
+            ConnectionState::Connecting { failed_attempts } => failed_attempts,
+            ConnectionState::Connected => 0,
+        };
+        let status = self
+            .peer_status
+            .read()
+            .await
+            .get(&service.get_url())
+            .cloned()
+            .unwrap_or_default();
+        Peer {
+            id: self.identify(&mut service).await.ok(),
+            url: service.get_url(),
+            state: self.peer_state(&service).await,
+            failed_connection_attempts,
+            consecutive_failures: status.consecutive_failures,
+            last_health: status.last_health,
+        }
+    }
+
+    /// Lists the connected services, along with their health as last probed.
+    #[tracing::instrument(fields(component = "Core"), skip(self))]
+    pub async fn peers(&self) -> Vec<Peer> {
+        let connected_services = self.connected_services.read().await.clone();
+        join_all(
+            connected_services
+                .into_iter()
+                .map(|service| self.peer(service)),
+        )
+        .await
+    }
+
+    /// Peers changed through [`Core::add_peer`] and [`Core::remove_peer`].
+    pub async fn manual_peers(&self) -> ManualPeers {
+        self.manual_peers.read().await.clone()
+    }
+
+    /// Adds the service at `url` to the connected services, keeping it connected whatever
+    /// discovery finds afterwards.
+    #[tracing::instrument(fields(component = "Core"), skip(self))]
+    pub async fn add_peer(&self, url: String) -> Result<Peer, CoreError<S::E, C::E>> {
+        let peer = self.connect_peer(url.clone()).await?;
+        let mut manual_peers = self.manual_peers.write().await;
+        manual_peers.removed.remove(&url);
+        manual_peers.added.insert(url);
+        Ok(peer)
+    }
+
+    /// Adds the service at `url` to the connected services, connecting to it in the background.
+    pub async fn connect_peer(&self, url: String) -> Result<Peer, CoreError<S::E, C::E>> {
+        if self.connected_urls().await.contains(&url) {
+            return Err(CoreError::PeerAlreadyConnected(url));
+        }
+
+        let service = C::connect(url.clone()).map_err(CoreError::ClientError)?;
+        if !self.add_service(service.clone()).await {
+            return Err(CoreError::PeerAlreadyConnected(url));
+        }
+        info!("Connecting to peer {0}", url);
+
+        Ok(self.peer(service).await)
+    }
+
+    /// Drops the connected service whose url or identifier is `id`, not connecting to it again
+    /// whatever discovery finds afterwards.
+    #[tracing::instrument(fields(component = "Core"), skip(self))]
+    pub async fn remove_peer(&self, id: &str) -> Result<(), CoreError<S::E, C::E>> {
+        let connected_services = self.connected_services.read().await.clone();
+        let url = match connected_services
+            .iter()
+            .find(|service| service.get_url() == id)
+        {
+            Some(service) => Some(service.get_url()),
+            // Peers that do not answer in time are not the one looked for
+            None => join_all(
+                connected_services
+                    .into_iter()
+                    .map(|mut service| async move {
+                        let service_id = self.identify(&mut service).await;
+                        matches!(service_id, Ok(service_id) if service_id == id)
+                            .then(|| service.get_url())
+                    }),
+            )
+            .await
+            .into_iter()
+            .flatten()
+            .next(),
+        };
+        let url = url.ok_or_else(|| CoreError::PeerNotFound(id.to_string()))?;
+
+        self.remove_service(&url).await;
+        let mut manual_peers = self.manual_peers.write().await;
+        manual_peers.added.remove(&url);
+        manual_peers.removed.insert(url.clone());
+        info!("Dropped peer {0}", url);
         Ok(())
     }
 
+    /// Checks that the store answers, keeping how long it took.
+    async fn check_store(&self) -> StoreHealth {
+        let start = Instant::now();
+        let result = self.store.ping().await;
+        StoreHealth {
+            healthy: result.is_ok(),
+            error: result.err().map(|e| format!("{:?}", e)),
+            latency: start.elapsed(),
+        }
+    }
+
+    /// Number of connected services chains may go to, that is healthy or degraded ones.
+    async fn healthy_peers(&self) -> usize {
+        let connected_services = self.connected_services.read().await.clone();
+        let mut healthy_peers = 0;
+        for service in connected_services.iter() {
+            if let PeerState::Healthy | PeerState::Degraded = self.peer_state(service).await {
+                healthy_peers += 1;
+            }
+        }
+        healthy_peers
+    }
+
+    /// Fails unless the store answers and enough peers are healthy for chains to be served.
     pub async fn ready_check(&self) -> Result<(), CoreError<S::E, C::E>> {
+        let store = self.check_store().await;
+        if let Some(e) = store.error {
+            error!("Store is unavailable: {0}", e);
+            return Err(CoreError::ServiceUnavailable);
+        }
+
+        let healthy_peers = self.healthy_peers().await;
+        if healthy_peers < self.config.min_healthy_peers {
+            warn!(
+                "Only {0} healthy peers, {1} are required",
+                healthy_peers, self.config.min_healthy_peers
+            );
+            return Err(CoreError::ServiceUnavailable);
+        }
         Ok(())
     }
 
+    /// Reports the health of the store and of each peer, along with the readiness they lead to.
     #[tracing::instrument(fields(component = "Core"), skip(self))]
-    pub async fn get_word(&self, word: String) -> Result<String, CoreError<S::E, C::E>> {
+    pub async fn health_details(&self) -> HealthDetails {
+        let store = self.check_store().await;
+        let peers = self.peers().await;
+        let healthy_peers = peers
+            .iter()
+            .filter(|peer| matches!(peer.state, PeerState::Healthy | PeerState::Degraded))
+            .count();
+        HealthDetails {
+            ready: store.healthy && healthy_peers >= self.config.min_healthy_peers,
+            store,
+            healthy_peers,
+            min_healthy_peers: self.config.min_healthy_peers,
+            peers,
+        }
+    }
+
+    #[tracing::instrument(fields(component = "Core"), skip(self))]
+    pub async fn get_word(&self, word: String) -> Result<WordRecord, CoreError<S::E, C::E>> {
         info!(
             component = "Core",
             method = "get_word",
@@ -86,7 +886,11 @@ impl<S: Store, C: Client> Core<S, C> {
     }
 
     #[tracing::instrument(fields(component = "Core"), skip(self))]
-    pub async fn add_word(&mut self, word: String) -> Result<(), CoreError<S::E, C::E>> {
+    pub async fn add_word(
+        &mut self,
+        word: String,
+        metadata: WordMetadata,
+    ) -> Result<(), CoreError<S::E, C::E>> {
         info!(
             component = "Core",
             method = "add_word",
@@ -95,9 +899,16 @@ impl<S: Store, C: Client> Core<S, C> {
             word,
         );
 
+        check_word(&word)?;
+        check_weight(metadata.weight)?;
+
         Ok(self
             .store
-            .add_word(word.clone())
+            .add_word(WordRecord::new(
+                word.clone(),
+                metadata,
+                &self.config.service_id,
+            ))
             .await
             .map_err(|err| match err {
                 StoreError::AlreadyExists(word) => CoreError::AlreadyExists(word),
@@ -131,6 +942,111 @@ impl<S: Store, C: Client> Core<S, C> {
             })?)
     }
 
+    #[tracing::instrument(fields(component = "Core"), skip(self, words))]
+    pub async fn add_words(
+        &mut self,
+        words: Vec<(String, WordMetadata)>,
+    ) -> Result<Vec<AddWordStatus>, CoreError<S::E, C::E>> {
+        info!(
+            component = "Core",
+            method = "add_words",
+            monotonic_counter.num_call = 1_u64,
+            histogram.batch_size = words.len() as u64,
+            "Adding {0} words...",
+            words.len(),
+        );
+
+        for (word, metadata) in words.iter() {
+            check_word(word)?;
+            check_weight(metadata.weight)?;
+        }
+
+        let records = words
+            .into_iter()
+            .map(|(word, metadata)| WordRecord::new(word, metadata, &self.config.service_id))
+            .collect();
+
+        self.store.add_words(records).await.map_err(|err| {
+            error!("Unanticipated error adding words: {:?}", err);
+            CoreError::StoreError(err)
+        })
+    }
+
+    #[tracing::instrument(fields(component = "Core"), skip(self))]
+    pub async fn set_weight(
+        &mut self,
+        word: String,
+        weight: f64,
+    ) -> Result<(), CoreError<S::E, C::E>> {
+        info!(
+            component = "Core",
+            method = "set_weight",
+            monotonic_counter.num_call = 1_u64,
+            "Setting weight of word {0} to {1}...",
+            word,
+            weight,
+        );
+
+        check_weight(Some(weight))?;
+
+        self.store
+            .set_weight(word.clone(), weight)
+            .await
+            .map_err(|err| match err {
+                StoreError::NotFound(word) => CoreError::NotFound(word),
+                _ => {
+                    error!(
+                        "Unanticipated error setting weight of word {:0}: {:?}",
+                        word, err
+                    );
+                    CoreError::StoreError(err)
+                }
+            })
+    }
+
+    #[tracing::instrument(fields(component = "Core"), skip(self))]
+    pub async fn list_words(
+        &self,
+        cursor: Option<String>,
+        limit: usize,
+        prefix: Option<String>,
+    ) -> Result<WordPage, CoreError<S::E, C::E>> {
+        info!(
+            component = "Core",
+            method = "list_words",
+            monotonic_counter.num_call = 1_u64,
+            "Listing words..."
+        );
+
+        self.store
+            .list_words(cursor, limit.clamp(1, MAX_LIST_LIMIT), prefix)
+            .await
+            .map_err(|err| {
+                error!("Unanticipated error listing words: {:?}", err);
+                CoreError::StoreError(err)
+            })
+    }
+
+    #[tracing::instrument(fields(component = "Core"), skip(self))]
+    pub fn iter_words(&self) -> WordStream<S::E, C::E> {
+        info!(
+            component = "Core",
+            method = "iter_words",
+            monotonic_counter.num_call = 1_u64,
+            "Iterating over words..."
+        );
+
+        self.store
+            .iter_words()
+            .map(|result| {
+                result.map_err(|err| {
+                    error!("Unanticipated error iterating over words: {:?}", err);
+                    CoreError::StoreError(err)
+                })
+            })
+            .boxed()
+    }
+
     #[tracing::instrument(fields(component = "Core"), skip(self))]
     pub async fn random_word(&self) -> Result<String, CoreError<S::E, C::E>> {
         info!(
@@ -148,34 +1064,252 @@ impl<S: Store, C: Client> Core<S, C> {
     }
 
     #[tracing::instrument(fields(component = "Core"), skip(self))]
-    pub async fn chain(
+    pub async fn chain(&self, state: ChainState) -> Result<ChainResult, CoreError<S::E, C::E>> {
+        match self.extend(state).await? {
+            Extension::Done(result) => Ok(result),
+            Extension::Forward(state, strategy) => match state.options.mode {
+                ChainMode::Recursive => self.chain_with_service(state, strategy.as_ref()).await,
+                ChainMode::Orchestrated => self.orchestrate(state, strategy.as_ref(), None).await,
+            },
+        }
+    }
+
+    /// Builds a chain like [`Core::chain`], emitting each word as soon as the
+    /// service picking it answers instead of waiting for the whole chain.
+    pub fn chain_stream(&self, state: ChainState) -> ChainEventStream<S::E, C::E> {
+        let core = self.clone();
+        let (sender, receiver) = mpsc::channel(CHAIN_STREAM_BUFFER);
+        tokio::spawn(
+            async move {
+                if let Err(err) = core.stream_chain(state, &sender).await {
+                    // The receiver may already be gone, there is no one left to tell then
+                    let _ = sender.send(Err(err)).await;
+                }
+            }
+            .in_current_span(),
+        );
+        ReceiverStream::new(receiver).boxed()
+    }
+
+    #[tracing::instrument(fields(component = "Core"), skip(self, sender))]
+    async fn stream_chain(
         &self,
-        chain: Vec<String>,
-        count: u32,
-    ) -> Result<Vec<String>, CoreError<S::E, C::E>> {
-        let random_word = self.select_random_word().await?;
+        state: ChainState,
+        sender: &ChainEventSender<S::E, C::E>,
+    ) -> Result<(), CoreError<S::E, C::E>> {
+        let previous_hops = state.trace.len();
+        let (state, strategy) = match self.extend(state).await? {
+            Extension::Done(result) => {
+                // Only the word of this service, if any, is new to the receiver
+                let new_elements = result.elements.iter().filter(|element| {
+                    element
+                        .provenance
+                        .as_ref()
+                        .is_some_and(|provenance| provenance.hop_index as usize >= previous_hops)
+                });
+                for element in new_elements {
+                    send_event(sender, ChainEvent::Element(element.clone())).await;
+                }
+                send_interruption(sender, result).await;
+                return Ok(());
+            }
+            Extension::Forward(state, strategy) => (state, strategy),
+        };
+
+        if let Some(element) = state.clone().into_elements().pop() {
+            if !send_event(sender, ChainEvent::Element(element)).await {
+                return Ok(());
+            }
+        }
+
+        let result = match state.options.mode {
+            ChainMode::Recursive => {
+                return self
+                    .stream_with_service(state, strategy.as_ref(), sender)
+                    .await
+            }
+            ChainMode::Orchestrated => {
+                self.orchestrate(state, strategy.as_ref(), Some(sender))
+                    .await?
+            }
+        };
+        send_interruption(sender, result).await;
+        Ok(())
+    }
+
+    /// Appends the word of this service to the chain, telling whether it should
+    /// be sent on to the connected services.
+    async fn extend(
+        &self,
+        mut state: ChainState,
+    ) -> Result<Extension<S, C>, CoreError<S::E, C::E>> {
+        let start = Instant::now();
+        check_limits(&self.config.limits, &state)?;
+        let kind = state.options.strategy.unwrap_or(self.config.chain_strategy);
+        let strategy = self.strategies.get(&kind).cloned().ok_or_else(|| {
+            CoreError::InvalidArgument(format!("Chain strategy {:?} is not available", kind))
+        })?;
+        // Following hops stick to the policy resolved by the first one
+        state
+            .options
+            .failure_policy
+            .get_or_insert(self.config.failure_policy);
+
+        let service_id = &self.config.service_id;
+        if state.options.avoid_revisiting_services && state.visited_services.contains(service_id) {
+            warn!("Chain already went through this service");
+            return Err(CoreError::UnsatisfiableChain(format!(
+                "Service {} was already visited by this chain",
+                service_id
+            )));
+        }
+
+        if state.is_expired() {
+            return state
+                .interrupt(CoreError::DeadlineExceeded)
+                .map(Extension::Done);
+        }
+
+        let next_word = match state.deadline {
+            Some(deadline) => {
+                match tokio::time::timeout_at(
+                    (deadline - DEADLINE_MARGIN).into(),
+                    self.select_next_word(strategy.as_ref(), &state),
+                )
+                .await
+                {
+                    Ok(next_word) => next_word?,
+                    Err(_) => {
+                        return state
+                            .interrupt(CoreError::DeadlineExceeded)
+                            .map(Extension::Done)
+                    }
+                }
+            }
+            None => self.select_next_word(strategy.as_ref(), &state).await?,
+        };
+        let Some(next_word) = next_word else {
+            info!("Chain strategy {:?} ended the chain", kind);
+            return Ok(Extension::Done(state.complete()));
+        };
         info!(
             component = "Core",
             method = "chain",
-            histogram.chain_count = count,
+            histogram.chain_count = state.count,
             monotonic_counter.num_call = 1_u64,
             "Adding word {0} to the chain...",
-            random_word,
+            next_word,
         );
 
-        let mut new_chain = chain.clone();
-        new_chain.push(random_word);
+        state.chain.push(next_word.clone());
+        if !state.visited_services.contains(service_id) {
+            state.visited_services.push(service_id.clone());
+        }
+        state.trace.push(Hop {
+            service_id: service_id.clone(),
+            word: next_word,
+            latency: start.elapsed(),
+        });
+        // Following hops stick to the strategy resolved here
+        state.options.strategy = Some(kind);
 
-        if count > 0 {
+        if strategy.should_continue(&state.chain, state.count) {
             if self.connected_services.read().await.is_empty() {
                 warn!("Chain was called because no services connected!");
                 return Err(CoreError::NoConnectedServices);
-            } else {
-                new_chain = self.chain_with_random_service(new_chain, count).await?;
             }
+            return Ok(Extension::Forward(state, strategy));
         }
 
-        Ok(new_chain)
+        Ok(Extension::Done(state.complete()))
+    }
+
+    /// Picks the word following the chain from this service's store only, for
+    /// a service orchestrating the chain.
+    #[tracing::instrument(fields(component = "Core"), skip(self))]
+    pub async fn next_word(
+        &self,
+        state: ChainState,
+    ) -> Result<Option<String>, CoreError<S::E, C::E>> {
+        info!(
+            component = "Core",
+            method = "next_word",
+            monotonic_counter.num_call = 1_u64,
+            "Picking the next word of the chain..."
+        );
+
+        check_limits(&self.config.limits, &state)?;
+        let kind = state.options.strategy.unwrap_or(self.config.chain_strategy);
+        let strategy = self.strategies.get(&kind).ok_or_else(|| {
+            CoreError::InvalidArgument(format!("Chain strategy {:?} is not available", kind))
+        })?;
+
+        match state.deadline {
+            Some(deadline) => tokio::time::timeout_at(
+                (deadline - DEADLINE_MARGIN).into(),
+                self.select_next_word(strategy.as_ref(), &state),
+            )
+            .await
+            .unwrap_or(Err(CoreError::DeadlineExceeded)),
+            None => self.select_next_word(strategy.as_ref(), &state).await,
+        }
+    }
+
+    /// Learns the word transitions of a whitespace-separated sentence, used by the Markov chain strategy.
+    #[tracing::instrument(fields(component = "Core"), skip(self))]
+    pub async fn learn_sentence(&mut self, sentence: String) -> Result<(), CoreError<S::E, C::E>> {
+        info!(
+            component = "Core",
+            method = "learn_sentence",
+            monotonic_counter.num_call = 1_u64,
+            "Learning sentence {0:?}...",
+            sentence,
+        );
+
+        let words: Vec<String> = sentence.split_whitespace().map(str::to_string).collect();
+        if words.len() < 2 {
+            return Err(CoreError::InvalidArgument(
+                "A sentence needs at least two words".to_string(),
+            ));
+        }
+
+        self.store.learn_sentence(words).await.map_err(|err| {
+            error!("Unanticipated error learning sentence: {:?}", err);
+            CoreError::StoreError(err)
+        })
+    }
+
+    /// Asks `strategy` for the next word, drawing again while it is already part
+    /// of the chain when the request wants unique words.
+    #[tracing::instrument(fields(component = "Core"), skip(self, strategy))]
+    async fn select_next_word(
+        &self,
+        strategy: &dyn ChainStrategy<S, C>,
+        state: &ChainState,
+    ) -> Result<Option<String>, CoreError<S::E, C::E>> {
+        for _ in 0..MAX_UNIQUE_WORD_ATTEMPTS {
+            let next_word = strategy
+                .next_word(&self.store, &state.chain)
+                .await
+                .map_err(|err| match err {
+                    StoreError::Empty => CoreError::Empty,
+                    _ => {
+                        error!("Unanticipated error picking the next word: {:?}", err);
+                        CoreError::StoreError(err)
+                    }
+                })?;
+            match next_word {
+                Some(word) if state.options.unique_words && state.chain.contains(&word) => {
+                    debug!("Word {0} is already part of the chain, drawing again", word);
+                }
+                next_word => return Ok(next_word),
+            }
+        }
+
+        Err(CoreError::UnsatisfiableChain(format!(
+            "No word absent from the chain found after {} attempts",
+            MAX_UNIQUE_WORD_ATTEMPTS
+        )))
     }
 
     #[tracing::instrument(fields(component = "Core"), skip(self))]
@@ -193,35 +1327,765 @@ impl<S: Store, C: Client> Core<S, C> {
             })?)
     }
 
-    #[tracing::instrument(fields(component = "Core"), skip(self))]
-    async fn chain_with_random_service(
-        &self,
-        chain: Vec<String>,
-        count: u32,
-    ) -> Result<Vec<String>, CoreError<S::E, C::E>> {
-        let mut connected_services = self.connected_services.read().await.clone();
+    /// Lists the connected services the chain may go to next, leaving out the
+    /// visited ones when the request avoids revisiting services.
+    async fn eligible_services(&self, state: &ChainState) -> Result<Vec<C>, CoreError<S::E, C::E>> {
+        let connected_services = self.connected_services.read().await.clone();
         let num_connected_services = connected_services.len();
 
-        let random_service = connected_services
-            .get_mut(random_range(0..num_connected_services))
-            .ok_or(CoreError::IndexError)
-            .map_err(|err: CoreError<S::E, C::E>| {
-                error!("Error picking random service in list: {:?}", err);
-                CoreError::IndexError
-            })?;
+        let mut eligible_services = Vec::with_capacity(num_connected_services);
+        for mut service in connected_services {
+            match self.peer_state(&service).await {
+                PeerState::Connecting => {
+                    debug!(
+                        "Skipping service {0} that is still connecting",
+                        service.get_url()
+                    );
+                    continue;
+                }
+                PeerState::OpenCircuit => {
+                    debug!(
+                        "Skipping service {0} whose circuit is open",
+                        service.get_url()
+                    );
+                    continue;
+                }
+                PeerState::Healthy | PeerState::Degraded => {}
+            }
+            if state.options.avoid_revisiting_services {
+                match self.identify(&mut service).await {
+                    Ok(id) if state.visited_services.contains(&id) => {
+                        debug!("Skipping already visited service {0}", id);
+                        continue;
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        warn!(
+                            "Skipping service {:0} that could not be identified: {:?}",
+                            service.get_url(),
+                            e
+                        );
+                        continue;
+                    }
+                }
+            }
+            eligible_services.push(service);
+        }
+
+        if eligible_services.is_empty() {
+            warn!("No eligible service left to chain with");
+            return Err(CoreError::UnsatisfiableChain(format!(
+                "No eligible peer remains, the {} connected services were already visited, are still connecting or are unhealthy",
+                num_connected_services
+            )));
+        }
+
+        Ok(eligible_services)
+    }
+
+    /// Sends a request built from `state` to a peer picked by `strategy`, moving
+    /// on to another eligible peer on failure as long as the failure policy allows it.
+    async fn call_peer<T, F, Fut>(
+        &self,
+        state: &mut ChainState,
+        strategy: &dyn ChainStrategy<S, C>,
+        call: F,
+    ) -> Result<(C, T), CoreError<S::E, C::E>>
+    where
+        F: Fn(C, ChainState) -> Fut,
+        Fut: Future<Output = Result<T, ClientError<C::E>>>,
+    {
+        let max_attempts = match state.options.failure_policy.unwrap_or_default() {
+            FailurePolicy::RetryOtherPeer => self.config.max_hop_retries + 1,
+            _ => 1,
+        };
+
+        let mut failed_services = Vec::new();
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            let mut eligible_services = self.eligible_services(state).await?;
+            eligible_services.retain(|service| !failed_services.contains(&service.get_url()));
+            if eligible_services.is_empty() {
+                return Err(CoreError::HopFailed(format!(
+                    "All the {} eligible peers failed",
+                    failed_services.len()
+                )));
+            }
+
+            let index = strategy
+                .next_peer(&eligible_services, &state.chain)
+                .filter(|index| *index < eligible_services.len())
+                .ok_or_else(|| {
+                    error!("Error picking service in list");
+                    CoreError::IndexError
+                })?;
+            let service = eligible_services.swap_remove(index);
+            info!("Chaining with client: {:?}", service.get_url());
+
+            let mut request = state.clone();
+            request.deadline = state.deadline.map(|deadline| deadline - DEADLINE_MARGIN);
+            let response = match request.deadline {
+                Some(deadline) => {
+                    tokio::time::timeout_at(deadline.into(), call(service.clone(), request))
+                        .await
+                        .unwrap_or(Err(ClientError::DeadlineExceeded))
+                }
+                None => call(service.clone(), request).await,
+            };
+
+            match response {
+                Ok(value) => return Ok((service, value)),
+                Err(err) if !is_retryable(&err) => return Err(peer_error(err)),
+                Err(err) if attempt == max_attempts => {
+                    error!(
+                        "Error chaining to client {:?}: {:?}",
+                        service.get_url(),
+                        err
+                    );
+                    return Err(CoreError::ClientError(err));
+                }
+                Err(err) => {
+                    warn!(
+                        "Attempt {0}/{1} to chain with {2:?} failed, trying another peer: {3:?}",
+                        attempt,
+                        max_attempts,
+                        service.get_url(),
+                        err
+                    );
+                    failed_services.push(service.get_url());
+                    state.retries += 1;
+                    tracing::Span::current().record("retries", state.retries);
+                }
+            }
+        }
+    }
+
+    /// Builds the rest of the chain by asking peers for one word at a time.
+    #[tracing::instrument(
+        fields(component = "Core", failure_policy = ?state.options.failure_policy, retries = state.retries),
+        skip(self, strategy)
+    )]
+    async fn orchestrate(
+        &self,
+        mut state: ChainState,
+        strategy: &dyn ChainStrategy<S, C>,
+        sender: Option<&ChainEventSender<S::E, C::E>>,
+    ) -> Result<ChainResult, CoreError<S::E, C::E>> {
+        let mut remaining = state.count;
+        while strategy.should_continue(&state.chain, remaining) {
+            if state.is_expired() {
+                return state.interrupt(CoreError::DeadlineExceeded);
+            }
+
+            let start = Instant::now();
+            let (mut service, word) = match self
+                .call_peer(&mut state, strategy, |mut service, request| async move {
+                    service.next_word(request).await
+                })
+                .await
+            {
+                Ok(response) => response,
+                Err(err) => return state.interrupt(err),
+            };
+            let Some(word) = word else {
+                info!("Chain strategy ended the chain on {:?}", service.get_url());
+                return Ok(state.complete());
+            };
+
+            let service_id = self
+                .identify(&mut service)
+                .await
+                .unwrap_or_else(|_| service.get_url());
+            state.chain.push(word.clone());
+            if !state.visited_services.contains(&service_id) {
+                state.visited_services.push(service_id.clone());
+            }
+            state.trace.push(Hop {
+                service_id,
+                word,
+                latency: start.elapsed(),
+            });
+            remaining = remaining.saturating_sub(1);
+
+            if let Some(sender) = sender {
+                let element = state.clone().into_elements().pop();
+                if let Some(element) = element {
+                    if !send_event(sender, ChainEvent::Element(element)).await {
+                        return Ok(state.complete());
+                    }
+                }
+            }
+        }
+
+        Ok(state.complete())
+    }
+
+    #[tracing::instrument(
+        fields(component = "Core", failure_policy = ?state.options.failure_policy, retries = state.retries),
+        skip(self, strategy)
+    )]
+    async fn chain_with_service(
+        &self,
+        mut state: ChainState,
+        strategy: &dyn ChainStrategy<S, C>,
+    ) -> Result<ChainResult, CoreError<S::E, C::E>> {
+        if state.is_expired() {
+            return state.interrupt(CoreError::DeadlineExceeded);
+        }
 
-        info!("Chaining with client: {:?}", random_service.get_url());
+        let response = self
+            .call_peer(
+                &mut state,
+                strategy,
+                |mut service, mut request| async move {
+                    request.count = request.count.saturating_sub(1);
+                    service.chain(request).await
+                },
+            )
+            .await;
 
-        Ok(random_service
-            .chain(chain, count - 1)
+        match response {
+            Ok((_, result)) => Ok(result),
+            Err(err) => state.interrupt(err),
+        }
+    }
+
+    /// Sends the chain on to a peer through its streaming endpoint, relaying
+    /// the words of the following hops as they come.
+    ///
+    /// The failure policy only fully applies until the peer answers: words relayed by then
+    /// cannot be taken back, so a later failure is not retried with another peer and
+    /// [`FailurePolicy::RetryOtherPeer`] fails the stream like [`FailurePolicy::FailFast`],
+    /// while [`FailurePolicy::Partial`] still ends it with an interruption.
+    #[tracing::instrument(
+        fields(component = "Core", failure_policy = ?state.options.failure_policy, retries = state.retries),
+        skip(self, strategy, sender)
+    )]
+    async fn stream_with_service(
+        &self,
+        mut state: ChainState,
+        strategy: &dyn ChainStrategy<S, C>,
+        sender: &ChainEventSender<S::E, C::E>,
+    ) -> Result<(), CoreError<S::E, C::E>> {
+        if state.is_expired() {
+            send_interruption(sender, state.interrupt(CoreError::DeadlineExceeded)?).await;
+            return Ok(());
+        }
+
+        let mut events = match self
+            .call_peer(
+                &mut state,
+                strategy,
+                |mut service, mut request| async move {
+                    request.count = request.count.saturating_sub(1);
+                    service.chain_stream(request).await
+                },
+            )
             .await
-            .map_err(|err| {
-                error!(
-                    "Error chaining to client {:?}: {:?}",
-                    random_service.get_url(),
-                    err
-                );
-                CoreError::ClientError(err)
-            })?)
+        {
+            Ok((_, events)) => events,
+            Err(err) => {
+                send_interruption(sender, state.interrupt(err)?).await;
+                return Ok(());
+            }
+        };
+
+        loop {
+            let event = match state.deadline {
+                Some(deadline) => tokio::time::timeout_at(deadline.into(), events.next())
+                    .await
+                    .unwrap_or(Some(Err(ClientError::DeadlineExceeded))),
+                None => events.next().await,
+            };
+            match event {
+                Some(Ok(event)) => {
+                    if !send_event(sender, event).await {
+                        return Ok(());
+                    }
+                }
+                Some(Err(err)) => {
+                    // Words already relayed are not part of the state, only the interruption is
+                    // sent, and retrying another peer would relay them a second time
+                    send_interruption(sender, state.interrupt(peer_error(err))?).await;
+                    return Ok(());
+                }
+                None => return Ok(()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clients::fake::{self, FakeClient, FakeCore};
+
+    fn hop(service_id: &str, word: &str) -> Hop {
+        Hop {
+            service_id: service_id.to_string(),
+            word: word.to_string(),
+            latency: Duration::ZERO,
+        }
+    }
+
+    fn words(words: &[&str]) -> Vec<String> {
+        words.iter().map(|word| word.to_string()).collect()
+    }
+
+    /// Instances `a`, `b` and `c`, each holding a single word and only connected to the next one,
+    /// along with the clients `a` and `b` reach the next one through.
+    async fn line() -> (FakeCore, FakeClient, FakeClient) {
+        let a = fake::core(fake::config("a"), &["alpha"]);
+        let b = fake::core(fake::config("b"), &["beta"]);
+        let c = fake::core(fake::config("c"), &["gamma"]);
+        let to_b = FakeClient::new(b.clone());
+        let to_c = FakeClient::new(c);
+        a.connected_services.write().await.push(to_b.clone());
+        b.connected_services.write().await.push(to_c.clone());
+        (a, to_b, to_c)
+    }
+
+    fn options(mode: ChainMode) -> ChainOptions {
+        ChainOptions {
+            strategy: Some(ChainStrategyKind::Random),
+            avoid_revisiting_services: false,
+            unique_words: true,
+            allow_partial: true,
+            mode,
+            failure_policy: Some(FailurePolicy::Partial),
+        }
+    }
+
+    fn element(word: &str, provenance: Option<(&str, u32)>) -> (String, Option<(String, u32)>) {
+        (
+            word.to_string(),
+            provenance.map(|(service_id, hop_index)| (service_id.to_string(), hop_index)),
+        )
+    }
+
+    /// Words of `elements` along with the service and hop they come from, latencies aside.
+    fn elements(elements: Vec<ChainElement>) -> Vec<(String, Option<(String, u32)>)> {
+        elements
+            .into_iter()
+            .map(|element| {
+                let provenance = element
+                    .provenance
+                    .map(|provenance| (provenance.service_id, provenance.hop_index));
+                (element.word, provenance)
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn input_is_limited_whatever_the_trace() {
+        let mut config = fake::config("a");
+        config.limits.max_chain_count = 2;
+        config.limits.max_input_length = 1;
+        let core = fake::core(config, &["alpha"]);
+
+        let state = ChainState {
+            chain: words(&["one", "two", "three"]),
+            trace: vec![hop("b", "two"), hop("c", "three")],
+            ..Default::default()
+        };
+        assert!(core.chain(state).await.is_ok());
+
+        // Hops that did not add the last words of the chain
+        let state = ChainState {
+            chain: words(&["one", "two", "three"]),
+            trace: vec![hop("b", "x"), hop("c", "y")],
+            ..Default::default()
+        };
+        assert!(matches!(
+            core.chain(state).await,
+            Err(CoreError::InvalidArgument(_))
+        ));
+
+        // More hops than a chain goes through
+        let state = ChainState {
+            chain: words(&["one", "two", "three", "four"]),
+            trace: vec![hop("b", "two"), hop("c", "three"), hop("d", "four")],
+            ..Default::default()
+        };
+        assert!(matches!(
+            core.chain(state).await,
+            Err(CoreError::InvalidArgument(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn next_word_applies_the_chain_limits() {
+        let mut config = fake::config("a");
+        config.limits.max_input_length = 1;
+        config.limits.max_word_length = 5;
+        let core = fake::core(config, &["alpha"]);
+
+        let state = ChainState {
+            chain: words(&["one"]),
+            ..Default::default()
+        };
+        assert_eq!(
+            core.next_word(state).await.unwrap(),
+            Some("alpha".to_string())
+        );
+
+        let state = ChainState {
+            chain: words(&["one", "two"]),
+            ..Default::default()
+        };
+        assert!(matches!(
+            core.next_word(state).await,
+            Err(CoreError::InvalidArgument(_))
+        ));
+
+        let state = ChainState {
+            chain: words(&["eleven"]),
+            ..Default::default()
+        };
+        assert!(matches!(
+            core.next_word(state).await,
+            Err(CoreError::InvalidArgument(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn recursive_chains_pass_options_and_provenance_on() {
+        let (a, to_b, to_c) = line().await;
+        let state = ChainState {
+            chain: words(&["start"]),
+            count: 2,
+            options: options(ChainMode::Recursive),
+            ..Default::default()
+        };
+
+        let result = a.chain(state).await.unwrap();
+        assert_eq!(
+            elements(result.elements),
+            vec![
+                element("start", None),
+                element("alpha", Some(("a", 0))),
+                element("beta", Some(("b", 1))),
+                element("gamma", Some(("c", 2))),
+            ]
+        );
+        assert_eq!(result.failure_policy, FailurePolicy::Partial);
+        for received in [to_b.received(), to_c.received()] {
+            assert_eq!(received.len(), 1);
+            assert_eq!(received[0].options, options(ChainMode::Recursive));
+        }
+        assert_eq!(to_c.received()[0].visited_services, vec!["a", "b"]);
+    }
+
+    #[tokio::test]
+    async fn orchestrated_chains_pass_options_and_provenance_on() {
+        let (a, to_b, to_c) = line().await;
+        let state = ChainState {
+            chain: words(&["start"]),
+            count: 1,
+            options: options(ChainMode::Orchestrated),
+            ..Default::default()
+        };
+
+        let result = a.chain(state).await.unwrap();
+        assert_eq!(
+            elements(result.elements),
+            vec![
+                element("start", None),
+                element("alpha", Some(("a", 0))),
+                element("beta", Some(("b", 1))),
+            ]
+        );
+        // The orchestrating instance asks for each word itself
+        let received = to_b.received();
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0].options, options(ChainMode::Orchestrated));
+        assert_eq!(received[0].chain, words(&["start", "alpha"]));
+        assert!(to_c.received().is_empty());
+    }
+
+    #[tokio::test]
+    async fn streamed_chains_pass_options_and_provenance_on() {
+        let (a, to_b, to_c) = line().await;
+        let state = ChainState {
+            chain: words(&["start"]),
+            count: 2,
+            options: options(ChainMode::Recursive),
+            ..Default::default()
+        };
+
+        let events: Vec<ChainEvent> = a
+            .chain_stream(state)
+            .map(|event| event.unwrap())
+            .collect()
+            .await;
+        let streamed = events
+            .into_iter()
+            .map(|event| match event {
+                ChainEvent::Element(element) => element,
+                event => panic!("unexpected event {event:?}"),
+            })
+            .collect();
+        assert_eq!(
+            elements(streamed),
+            vec![
+                element("alpha", Some(("a", 0))),
+                element("beta", Some(("b", 1))),
+                element("gamma", Some(("c", 2))),
+            ]
+        );
+        for received in [to_b.received(), to_c.received()] {
+            assert_eq!(received.len(), 1);
+            assert_eq!(received[0].options, options(ChainMode::Recursive));
+        }
+    }
+
+    #[tokio::test]
+    async fn deadlines_shrink_along_the_chain() {
+        let (a, to_b, to_c) = line().await;
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let state = ChainState {
+            chain: words(&["start"]),
+            count: 2,
+            deadline: Some(deadline),
+            ..Default::default()
+        };
+
+        let result = a.chain(state).await.unwrap();
+        assert_eq!(result.elements.len(), 4);
+        assert_eq!(result.interruption, None);
+        // Each hop keeps a margin to send its answer back
+        assert_eq!(
+            to_b.received()[0].deadline,
+            Some(deadline - DEADLINE_MARGIN)
+        );
+        assert_eq!(
+            to_c.received()[0].deadline,
+            Some(deadline - DEADLINE_MARGIN * 2)
+        );
+    }
+
+    #[tokio::test]
+    async fn expired_deadlines_stop_the_chain() {
+        let (a, to_b, _) = line().await;
+        let state = ChainState {
+            chain: words(&["start"]),
+            count: 2,
+            deadline: Some(Instant::now() + DEADLINE_MARGIN / 2),
+            ..Default::default()
+        };
+        assert!(matches!(
+            a.chain(state.clone()).await,
+            Err(CoreError::DeadlineExceeded)
+        ));
+
+        let mut state = state;
+        state.options.allow_partial = true;
+        let result = a.chain(state).await.unwrap();
+        assert_eq!(elements(result.elements), vec![element("start", None)]);
+        assert_eq!(result.interruption, Some(Interruption::DeadlineExceeded));
+        assert!(to_b.received().is_empty());
+    }
+
+    #[tokio::test]
+    async fn deadlines_expiring_on_the_way_stop_the_chain_there() {
+        let (a, to_b, to_c) = line().await;
+        // Enough for the first instance, not once the margin is taken off for the next one
+        let state = ChainState {
+            chain: words(&["start"]),
+            count: 2,
+            deadline: Some(Instant::now() + DEADLINE_MARGIN * 3 / 2),
+            options: ChainOptions {
+                allow_partial: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let result = a.chain(state).await.unwrap();
+        assert_eq!(
+            elements(result.elements),
+            vec![element("start", None), element("alpha", Some(("a", 0)))]
+        );
+        assert_eq!(result.interruption, Some(Interruption::DeadlineExceeded));
+        assert_eq!(to_b.received().len(), 1);
+        assert!(to_c.received().is_empty());
+    }
+
+    #[tokio::test]
+    async fn failed_hops_are_retried_with_another_peer() {
+        let a = fake::core(fake::config("a"), &["alpha"]);
+        let to_b = FakeClient::new(fake::core(fake::config("b"), &["beta"]));
+        let to_c = FakeClient::new(fake::core(fake::config("c"), &["gamma"]));
+        a.connected_services
+            .write()
+            .await
+            .extend([to_b.clone(), to_c.clone()]);
+        to_b.set_down(true);
+        // Round robin tries the peers in order, starting with the one that is down
+        let state = ChainState {
+            chain: words(&["start"]),
+            count: 1,
+            options: ChainOptions {
+                strategy: Some(ChainStrategyKind::RoundRobin),
+                failure_policy: Some(FailurePolicy::RetryOtherPeer),
+                ..options(ChainMode::Recursive)
+            },
+            ..Default::default()
+        };
+
+        let result = a.chain(state).await.unwrap();
+        assert_eq!(
+            elements(result.elements),
+            vec![
+                element("start", None),
+                element("alpha", Some(("a", 0))),
+                element("gamma", Some(("c", 1))),
+            ]
+        );
+        assert_eq!(result.interruption, None);
+        assert_eq!(result.retries, 1);
+        assert_eq!(to_b.received().len(), 1);
+        assert_eq!(to_c.received().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn partial_chains_end_at_the_failed_hop() {
+        let (a, to_b, to_c) = line().await;
+        to_c.set_down(true);
+        let state = ChainState {
+            chain: words(&["start"]),
+            count: 2,
+            options: options(ChainMode::Recursive),
+            ..Default::default()
+        };
+
+        let result = a.chain(state).await.unwrap();
+        assert_eq!(
+            elements(result.elements),
+            vec![
+                element("start", None),
+                element("alpha", Some(("a", 0))),
+                element("beta", Some(("b", 1))),
+            ]
+        );
+        assert_eq!(result.interruption, Some(Interruption::PeerFailed));
+        assert!(result.error.is_some());
+        assert_eq!(to_b.received().len(), 1);
+        assert_eq!(to_c.received().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn words_with_line_breaks_are_rejected() {
+        let mut core = fake::core(fake::config("a"), &[]);
+        for word in ["two\nwords", "two\r\nwords"] {
+            assert!(matches!(
+                core.add_word(word.to_string(), WordMetadata::default())
+                    .await,
+                Err(CoreError::InvalidArgument(_))
+            ));
+            assert!(matches!(
+                core.add_words(vec![(word.to_string(), WordMetadata::default())])
+                    .await,
+                Err(CoreError::InvalidArgument(_))
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn peers_that_do_not_answer_are_left_unidentified() {
+        let (a, to_b, _) = line().await;
+        to_b.set_stalled(true);
+
+        let peers = tokio::time::timeout(Duration::from_secs(1), a.peers())
+            .await
+            .unwrap();
+        assert_eq!(peers.len(), 1);
+        assert_eq!(peers[0].id, None);
+
+        let state = ChainState {
+            chain: words(&["start"]),
+            count: 2,
+            options: ChainOptions {
+                avoid_revisiting_services: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let result = tokio::time::timeout(Duration::from_secs(1), a.chain(state))
+            .await
+            .unwrap();
+        assert!(matches!(result, Err(CoreError::UnsatisfiableChain(_))));
+        assert!(to_b.received().is_empty());
+    }
+
+    /// Records a probe of `status` answered in `latency` milliseconds, or failed when `None`,
+    /// returning the state it leads to.
+    fn probe(status: &mut PeerStatus, latency: Option<u64>) -> PeerState {
+        let health = PeerHealth {
+            healthy: latency.is_some(),
+            error: None,
+            latency: Duration::from_millis(latency.unwrap_or_default()),
+            checked_at: 0,
+        };
+        status.record(health, &fake::config("a").probe);
+        status.state
+    }
+
+    #[test]
+    fn failures_degrade_peers_then_open_their_circuit() {
+        let mut status = PeerStatus::default();
+        assert_eq!(probe(&mut status, None), PeerState::Degraded);
+        assert_eq!(probe(&mut status, None), PeerState::Degraded);
+        assert_eq!(probe(&mut status, None), PeerState::OpenCircuit);
+        assert_eq!(probe(&mut status, None), PeerState::OpenCircuit);
+
+        // Failures must be consecutive
+        let mut status = PeerStatus::default();
+        probe(&mut status, None);
+        probe(&mut status, None);
+        assert_eq!(probe(&mut status, Some(1)), PeerState::Healthy);
+        assert_eq!(probe(&mut status, None), PeerState::Degraded);
+    }
+
+    #[test]
+    fn open_circuits_close_after_enough_successes() {
+        let mut status = PeerStatus::default();
+        for _ in 0..3 {
+            probe(&mut status, None);
+        }
+        assert_eq!(probe(&mut status, Some(1)), PeerState::OpenCircuit);
+        // Successes must be consecutive
+        assert_eq!(probe(&mut status, None), PeerState::OpenCircuit);
+        assert_eq!(probe(&mut status, Some(1)), PeerState::OpenCircuit);
+        assert_eq!(probe(&mut status, Some(1)), PeerState::Healthy);
+    }
+
+    #[test]
+    fn slow_peers_are_degraded() {
+        let mut status = PeerStatus::default();
+        assert_eq!(probe(&mut status, Some(100)), PeerState::Degraded);
+        assert_eq!(probe(&mut status, Some(50)), PeerState::Healthy);
+
+        for _ in 0..3 {
+            probe(&mut status, None);
+        }
+        probe(&mut status, Some(100));
+        assert_eq!(probe(&mut status, Some(100)), PeerState::Degraded);
+    }
+
+    #[test]
+    fn recording_returns_the_previous_state() {
+        let mut status = PeerStatus::default();
+        let health = |healthy| PeerHealth {
+            healthy,
+            error: None,
+            latency: Duration::ZERO,
+            checked_at: 0,
+        };
+        let config = fake::config("a").probe;
+        assert_eq!(status.record(health(false), &config), PeerState::Healthy);
+        assert_eq!(status.record(health(true), &config), PeerState::Degraded);
+        assert!(status.last_health.is_some_and(|health| health.healthy));
     }
 }
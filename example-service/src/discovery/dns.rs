@@ -0,0 +1,164 @@
+use crate::discovery::{Discovery, DiscoveryError};
+use futures::future::join_all;
+use hickory_resolver::{ResolveError, TokioResolver};
+use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, SocketAddr};
+use tonic::async_trait;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum DnsRecord {
+    /// Each record gives the host and port of a peer
+    Srv,
+    /// Each address of the name is a peer, as with a Kubernetes headless service
+    A,
+}
+
+/// Peers found by polling a DNS name, answers being cached for their TTL.
+#[derive(Clone, Debug)]
+pub struct DnsDiscovery {
+    resolver: TokioResolver,
+    name: String,
+    record: DnsRecord,
+    /// gRPC port of the peers, for A records which carry none
+    port: u16,
+    /// Address of the local instance, left out of the peers found
+    local_ip: Option<IpAddr>,
+}
+
+impl DnsDiscovery {
+    /// Builds a resolver from the system configuration.
+    pub fn new(
+        name: String,
+        record: DnsRecord,
+        port: u16,
+        local_ip: Option<IpAddr>,
+    ) -> Result<Self, DiscoveryError> {
+        let resolver = TokioResolver::builder_tokio()
+            .map_err(DiscoveryError::Resolver)?
+            .build();
+
+        Ok(DnsDiscovery {
+            resolver,
+            name,
+            record,
+            port,
+            local_ip,
+        })
+    }
+
+    /// Urls of the targets of the SRV records of the name, resolved to tell which one is the local
+    /// instance when its address is known.
+    async fn srv_urls(&self) -> Result<Vec<String>, ResolveError> {
+        let lookup = self.resolver.srv_lookup(self.name.as_str()).await?;
+        let targets = lookup.iter().map(|srv| async move {
+            let host = srv.target().to_utf8().trim_end_matches('.').to_string();
+            // A target that cannot be resolved is kept, as it cannot be told apart from the others
+            let ips = match self.local_ip {
+                Some(_) => self
+                    .resolver
+                    .lookup_ip(host.as_str())
+                    .await
+                    .map(|ips| ips.iter().collect())
+                    .unwrap_or_default(),
+                None => Vec::new(),
+            };
+            (host, srv.port(), ips)
+        });
+        Ok(srv_record_urls(join_all(targets).await, self.local_ip))
+    }
+
+    fn lookup_error(&self, source: ResolveError) -> DiscoveryError {
+        DiscoveryError::Lookup {
+            source,
+            name: self.name.clone(),
+        }
+    }
+}
+
+/// Urls of the peers found at `ips`, the local instance being part of the answer when it is
+/// behind the name looked up.
+fn a_record_urls(
+    ips: impl Iterator<Item = IpAddr>,
+    port: u16,
+    local_ip: Option<IpAddr>,
+) -> Vec<String> {
+    ips.filter(|ip| Some(*ip) != local_ip)
+        .map(|ip| format!("http://{0}", SocketAddr::new(ip, port)))
+        .collect()
+}
+
+/// Urls of the peers at the `(host, port, ips)` targets of SRV records, leaving out the one
+/// resolving to the local instance.
+fn srv_record_urls(
+    targets: impl IntoIterator<Item = (String, u16, Vec<IpAddr>)>,
+    local_ip: Option<IpAddr>,
+) -> Vec<String> {
+    targets
+        .into_iter()
+        .filter(|(_, _, ips)| local_ip.is_none_or(|local_ip| !ips.contains(&local_ip)))
+        .map(|(host, port, _)| format!("http://{0}:{1}", host, port))
+        .collect()
+}
+
+#[async_trait]
+impl Discovery for DnsDiscovery {
+    async fn discover(&mut self) -> Result<Vec<String>, DiscoveryError> {
+        let urls = match self.record {
+            DnsRecord::Srv => self.srv_urls().await,
+            DnsRecord::A => self
+                .resolver
+                .lookup_ip(self.name.as_str())
+                .await
+                .map(|lookup| a_record_urls(lookup.iter(), self.port, self.local_ip)),
+        };
+
+        match urls {
+            Ok(mut urls) => {
+                urls.sort();
+                urls.dedup();
+                Ok(urls)
+            }
+            // Every peer is gone
+            Err(e) if e.is_no_records_found() => Ok(Vec::new()),
+            Err(e) => Err(self.lookup_error(e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_records_leave_out_the_local_instance() {
+        let ips = ["10.0.0.1", "10.0.0.2", "10.0.0.3"].map(|ip| ip.parse().unwrap());
+        let local_ip = "10.0.0.2".parse().ok();
+
+        assert_eq!(
+            a_record_urls(ips.into_iter(), 50051, local_ip),
+            vec!["http://10.0.0.1:50051", "http://10.0.0.3:50051"]
+        );
+        assert_eq!(a_record_urls(ips.into_iter(), 50051, None).len(), 3);
+    }
+
+    #[test]
+    fn srv_records_leave_out_the_local_instance() {
+        let targets = [
+            ("peer-0.words", vec!["10.0.0.1"]),
+            ("peer-1.words", vec!["10.0.0.2"]),
+            ("peer-2.words", vec![]),
+        ]
+        .map(|(host, ips)| {
+            let ips = ips.into_iter().map(|ip| ip.parse().unwrap()).collect();
+            (host.to_string(), 50051, ips)
+        });
+        let local_ip = "10.0.0.2".parse().ok();
+
+        assert_eq!(
+            srv_record_urls(targets.clone(), local_ip),
+            vec!["http://peer-0.words:50051", "http://peer-2.words:50051"]
+        );
+        assert_eq!(srv_record_urls(targets, None).len(), 3);
+    }
+}
@@ -0,0 +1,59 @@
+use crate::discovery::{Discovery, DiscoveryError};
+use std::path::PathBuf;
+use std::time::SystemTime;
+use tonic::async_trait;
+use tracing::debug;
+
+/// Peers listed in a file, one url per line, which is read again whenever it is modified.
+///
+/// Blank lines and lines starting with `#` are ignored, so a mounted ConfigMap can document its peers.
+#[derive(Clone, Debug)]
+pub struct FileDiscovery {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    urls: Vec<String>,
+}
+
+impl FileDiscovery {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        FileDiscovery {
+            path: path.into(),
+            modified: None,
+            urls: Vec::new(),
+        }
+    }
+
+    fn io_error(&self, source: std::io::Error) -> DiscoveryError {
+        DiscoveryError::File {
+            source,
+            path: self.path.clone(),
+        }
+    }
+}
+
+#[async_trait]
+impl Discovery for FileDiscovery {
+    async fn discover(&mut self) -> Result<Vec<String>, DiscoveryError> {
+        let modified = tokio::fs::metadata(&self.path)
+            .await
+            .and_then(|metadata| metadata.modified())
+            .map_err(|e| self.io_error(e))?;
+        if self.modified == Some(modified) {
+            return Ok(self.urls.clone());
+        }
+
+        debug!("Reading peers from {:?}", self.path);
+        let content = tokio::fs::read_to_string(&self.path)
+            .await
+            .map_err(|e| self.io_error(e))?;
+        self.urls = content
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(String::from)
+            .collect();
+        self.modified = Some(modified);
+
+        Ok(self.urls.clone())
+    }
+}
@@ -0,0 +1,134 @@
+use crate::clients::Client;
+use crate::core::Core;
+use crate::stores::Store;
+use hickory_resolver::ResolveError;
+use std::fmt::Debug;
+use std::path::PathBuf;
+use std::time::Duration;
+use thiserror::Error;
+use tokio::time::MissedTickBehavior;
+use tonic::async_trait;
+use tracing::{error, info, warn};
+
+pub mod dns;
+pub mod file;
+pub mod static_list;
+
+#[derive(Error, Debug)]
+pub enum DiscoveryError {
+    #[error("Failed to build DNS resolver")]
+    Resolver(#[source] ResolveError),
+    #[error("DNS lookup of {name:?} failed")]
+    Lookup {
+        #[source]
+        source: ResolveError,
+        name: String,
+    },
+    #[error("Failed to read peers file {path:?}")]
+    File {
+        #[source]
+        source: std::io::Error,
+        path: PathBuf,
+    },
+}
+
+/// Source of the gRPC urls of the peers chains are sent to.
+#[async_trait]
+pub trait Discovery: Send + Sync + Debug {
+    /// Lists the urls of the peers currently known, peers missing from the list being dropped.
+    async fn discover(&mut self) -> Result<Vec<String>, DiscoveryError>;
+}
+
+/// Keeps the connected services of `core` in line with the peers found by `discovery`.
+///
+/// Peers are kept as they are when `discovery` fails, so that a flaky source does not empty them,
+/// and peers added or removed through the API are left as they are.
+pub async fn run_discovery<S: Store, C: Client>(
+    mut discovery: Box<dyn Discovery>,
+    core: Core<S, C>,
+    interval: Duration,
+) {
+    let mut ticker = tokio::time::interval(interval);
+    // Connecting to new peers may take longer than the interval
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        ticker.tick().await;
+        match discovery.discover().await {
+            Ok(urls) => sync_services(&core, urls).await,
+            Err(e) => error!("Failed to discover peers with {:?}: {:?}", discovery, e),
+        }
+    }
+}
+
+#[tracing::instrument(fields(component = "Discovery"), skip(core))]
+async fn sync_services<S: Store, C: Client>(core: &Core<S, C>, urls: Vec<String>) {
+    let connected_urls = core.connected_urls().await;
+    let manual_peers = core.manual_peers().await;
+
+    for url in connected_urls
+        .iter()
+        .filter(|url| !urls.contains(url) && !manual_peers.added.contains(*url))
+    {
+        if core.remove_service(url).await {
+            info!("Dropped peer {0}, which is no longer discovered", url);
+        }
+    }
+
+    for url in urls
+        .into_iter()
+        .filter(|url| !connected_urls.contains(url) && !manual_peers.removed.contains(url))
+    {
+        // Tried again on the next discovery when it fails
+        if let Err(e) = core.connect_peer(url.clone()).await {
+            warn!("Failed to connect to discovered peer {0}: {1:?}", url, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clients::fake;
+
+    fn urls(urls: &[&str]) -> Vec<String> {
+        urls.iter().map(|url| url.to_string()).collect()
+    }
+
+    #[tokio::test]
+    async fn sync_follows_discovered_peers() {
+        let core = fake::core(fake::config("a"), &["hello"]);
+
+        sync_services(&core, urls(&["fake://b", "fake://c"])).await;
+        sync_services(&core, urls(&["fake://c", "fake://d"])).await;
+
+        let mut connected_urls = core.connected_urls().await;
+        connected_urls.sort();
+        assert_eq!(connected_urls, urls(&["fake://c", "fake://d"]));
+    }
+
+    #[tokio::test]
+    async fn sync_keeps_peers_changed_through_the_api() {
+        let core = fake::core(fake::config("a"), &["hello"]);
+        sync_services(&core, urls(&["fake://b", "fake://c"])).await;
+
+        core.add_peer("fake://manual".to_string()).await.unwrap();
+        core.remove_peer("fake://b").await.unwrap();
+        sync_services(&core, urls(&["fake://b", "fake://c"])).await;
+
+        let mut connected_urls = core.connected_urls().await;
+        connected_urls.sort();
+        assert_eq!(connected_urls, urls(&["fake://c", "fake://manual"]));
+    }
+
+    #[tokio::test]
+    async fn peers_added_again_are_discovered_again() {
+        let core = fake::core(fake::config("a"), &["hello"]);
+        sync_services(&core, urls(&["fake://b"])).await;
+
+        core.remove_peer("fake://b").await.unwrap();
+        core.add_peer("fake://b".to_string()).await.unwrap();
+        sync_services(&core, urls(&[])).await;
+
+        assert_eq!(core.connected_urls().await, urls(&["fake://b"]));
+    }
+}
@@ -0,0 +1,33 @@
+use crate::discovery::{Discovery, DiscoveryError};
+use tonic::async_trait;
+
+/// Peers given once and for all in the configuration.
+#[derive(Clone, Debug)]
+pub struct StaticDiscovery {
+    urls: Vec<String>,
+}
+
+impl StaticDiscovery {
+    /// Reads a comma separated list of urls.
+    pub fn new(urls: &str) -> Self {
+        StaticDiscovery {
+            urls: urls
+                .split(',')
+                .map(str::trim)
+                .filter(|url| !url.is_empty())
+                .map(String::from)
+                .collect(),
+        }
+    }
+
+    pub fn urls(&self) -> Vec<String> {
+        self.urls.clone()
+    }
+}
+
+#[async_trait]
+impl Discovery for StaticDiscovery {
+    async fn discover(&mut self) -> Result<Vec<String>, DiscoveryError> {
+        Ok(self.urls.clone())
+    }
+}
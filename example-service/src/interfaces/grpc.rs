@@ -1,17 +1,265 @@
 use crate::clients::Client;
-use crate::core::{Core, CoreError};
+use crate::core::{
+    ChainElement, ChainEvent, ChainMode, ChainOptions, ChainState, Core, CoreError, FailurePolicy,
+    Hop, Interruption, Peer, PeerState,
+};
+use crate::membership::{Member, MemberState, Membership};
 use crate::stores::Store;
+use crate::strategies::ChainStrategyKind;
+use futures::stream::{BoxStream, StreamExt};
+use std::error::Error;
 use std::net::SocketAddr;
+use std::time::{Duration, Instant};
 use thiserror::Error;
+use tokio::time::MissedTickBehavior;
+use tonic::metadata::MetadataMap;
+use tonic::server::NamedService;
 use tonic::{Request, Response, Status};
-use tracing::trace;
-use word::{word_service_server::WordService, ChainRequest, ChainResponse};
-use word::{HealthRequest, HealthResponse};
+use tonic_health::server::HealthReporter;
+use tonic_health::ServingStatus;
+use tracing::{info, trace};
+use word::admin_service_server::{AdminService, AdminServiceServer};
+use word::membership_service_server::MembershipService;
+use word::word_service_server::{WordService, WordServiceServer};
+use word::{chain_stream_response, ChainStreamResponse};
+use word::{AddPeerRequest, AddPeerResponse, ListPeersRequest, ListPeersResponse};
+use word::{ChainLimitsRequest, ChainLimitsResponse};
+use word::{ChainRequest, ChainResponse};
+use word::{GossipRequest, GossipResponse, IndirectPingRequest, IndirectPingResponse};
+use word::{HealthRequest, HealthResponse, ListWordsRequest, ListWordsResponse};
+use word::{LearnSentenceRequest, LearnSentenceResponse, NextWordRequest, NextWordResponse};
+use word::{RandomWordRequest, RandomWordResponse, SetWeightRequest, SetWeightResponse};
+use word::{RemovePeerRequest, RemovePeerResponse};
 
 pub mod word {
     tonic::include_proto!("word");
 }
 
+impl From<FailurePolicy> for word::FailurePolicy {
+    fn from(policy: FailurePolicy) -> Self {
+        match policy {
+            FailurePolicy::FailFast => word::FailurePolicy::FailFast,
+            FailurePolicy::RetryOtherPeer => word::FailurePolicy::RetryOtherPeer,
+            FailurePolicy::Partial => word::FailurePolicy::Partial,
+        }
+    }
+}
+
+impl From<word::FailurePolicy> for FailurePolicy {
+    fn from(policy: word::FailurePolicy) -> Self {
+        match policy {
+            word::FailurePolicy::FailFast => FailurePolicy::FailFast,
+            word::FailurePolicy::RetryOtherPeer => FailurePolicy::RetryOtherPeer,
+            word::FailurePolicy::Partial => FailurePolicy::Partial,
+        }
+    }
+}
+
+impl From<Hop> for word::Hop {
+    fn from(hop: Hop) -> Self {
+        word::Hop {
+            service_id: hop.service_id,
+            word: hop.word,
+            latency_us: hop.latency.as_micros() as u64,
+        }
+    }
+}
+
+impl From<word::Hop> for Hop {
+    fn from(hop: word::Hop) -> Self {
+        Hop {
+            service_id: hop.service_id,
+            word: hop.word,
+            latency: Duration::from_micros(hop.latency_us),
+        }
+    }
+}
+
+impl From<Member> for word::Member {
+    fn from(member: Member) -> Self {
+        let state = match member.state {
+            MemberState::Alive => word::MemberState::Alive,
+            MemberState::Suspect => word::MemberState::Suspect,
+            MemberState::Dead => word::MemberState::Dead,
+        };
+        word::Member {
+            id: member.id,
+            url: member.url,
+            incarnation: member.incarnation,
+            state: state.into(),
+        }
+    }
+}
+
+impl From<word::Member> for Member {
+    fn from(member: word::Member) -> Self {
+        let state = match member.state() {
+            word::MemberState::Alive => MemberState::Alive,
+            word::MemberState::Suspect => MemberState::Suspect,
+            word::MemberState::Dead => MemberState::Dead,
+        };
+        Member {
+            id: member.id,
+            url: member.url,
+            incarnation: member.incarnation,
+            state,
+        }
+    }
+}
+
+/// Encoded descriptors of `proto/word.proto`, served by gRPC reflection.
+pub const FILE_DESCRIPTOR_SET: &[u8] = tonic::include_file_descriptor_set!("word_descriptor");
+
+/// Number of words listed when the request does not set a limit.
+const DEFAULT_LIST_LIMIT: usize = 100;
+
+fn chain_strategy_kind(strategy: word::ChainStrategy) -> ChainStrategyKind {
+    match strategy {
+        word::ChainStrategy::Random => ChainStrategyKind::Random,
+        word::ChainStrategy::NoRepeat => ChainStrategyKind::NoRepeat,
+        word::ChainStrategy::Markov => ChainStrategyKind::Markov,
+        word::ChainStrategy::RoundRobin => ChainStrategyKind::RoundRobin,
+    }
+}
+
+/// Parses the `grpc-timeout` header, made of at most 8 digits followed by a unit.
+fn grpc_timeout(metadata: &MetadataMap) -> Option<Duration> {
+    let value = metadata.get("grpc-timeout")?.to_str().ok()?;
+    if value.len() < 2 || value.len() > 9 {
+        return None;
+    }
+    let (amount, unit) = value.split_at(value.len() - 1);
+    let amount: u64 = amount.parse().ok()?;
+    match unit {
+        "H" => Some(Duration::from_secs(amount * 60 * 60)),
+        "M" => Some(Duration::from_secs(amount * 60)),
+        "S" => Some(Duration::from_secs(amount)),
+        "m" => Some(Duration::from_millis(amount)),
+        "u" => Some(Duration::from_micros(amount)),
+        "n" => Some(Duration::from_nanos(amount)),
+        _ => None,
+    }
+}
+
+/// Builds the state of a chain from a request, the tightest of the
+/// grpc-timeout header and the message timeout setting its deadline.
+fn chain_state(request: Request<ChainRequest>) -> ChainState {
+    let deadline = grpc_timeout(request.metadata())
+        .into_iter()
+        .chain(request.get_ref().timeout_ms.map(Duration::from_millis))
+        .min()
+        .map(|timeout| Instant::now() + timeout);
+    let message = request.into_inner();
+    let strategy = message
+        .strategy
+        .map(|_| chain_strategy_kind(message.strategy()));
+    let mode = match message.mode() {
+        word::ChainMode::Recursive => ChainMode::Recursive,
+        word::ChainMode::Orchestrated => ChainMode::Orchestrated,
+    };
+    let failure_policy = message
+        .failure_policy
+        .map(|_| FailurePolicy::from(message.failure_policy()));
+    ChainState {
+        options: ChainOptions {
+            strategy,
+            avoid_revisiting_services: message.avoid_revisiting_services,
+            unique_words: message.unique_words,
+            allow_partial: message.allow_partial,
+            mode,
+            failure_policy,
+        },
+        chain: message.input,
+        count: message.count,
+        visited_services: message.visited_services,
+        trace: message.trace.into_iter().map(Hop::from).collect(),
+        deadline,
+        retries: message.retries,
+    }
+}
+
+fn chain_status<SE: Error, CE: Error>(err: CoreError<SE, CE>) -> Status {
+    match err {
+        CoreError::NoConnectedServices => {
+            <GrpcInterfaceError as Into<Status>>::into(GrpcInterfaceError::BadRequest(
+                "This service is not connected to an example-service".to_string(),
+            ))
+        }
+        CoreError::Empty => <GrpcInterfaceError as Into<Status>>::into(
+            GrpcInterfaceError::BadRequest("The store is empty".to_string()),
+        ),
+        CoreError::UnsatisfiableChain(msg) => {
+            <GrpcInterfaceError as Into<Status>>::into(GrpcInterfaceError::FailedPrecondition(msg))
+        }
+        CoreError::InvalidArgument(msg) => {
+            <GrpcInterfaceError as Into<Status>>::into(GrpcInterfaceError::BadRequest(msg))
+        }
+        CoreError::DeadlineExceeded => GrpcInterfaceError::DeadlineExceeded.into(),
+        // Reported as aborted so that the previous hop does not retry a chain that already failed further on
+        CoreError::HopFailed(msg) => {
+            <GrpcInterfaceError as Into<Status>>::into(GrpcInterfaceError::Aborted(msg))
+        }
+        CoreError::ClientError(err) => {
+            <GrpcInterfaceError as Into<Status>>::into(GrpcInterfaceError::Aborted(err.to_string()))
+        }
+        _ => GrpcInterfaceError::InternalServerError.into(),
+    }
+}
+
+fn peer(peer: Peer) -> word::Peer {
+    let state = match peer.state {
+        PeerState::Connecting => word::PeerState::Connecting,
+        PeerState::Healthy => word::PeerState::Healthy,
+        PeerState::Degraded => word::PeerState::Degraded,
+        PeerState::OpenCircuit => word::PeerState::OpenCircuit,
+    };
+    word::Peer {
+        id: peer.id.unwrap_or_default(),
+        url: peer.url,
+        state: state.into(),
+        failed_connection_attempts: peer.failed_connection_attempts,
+        consecutive_failures: peer.consecutive_failures,
+        last_health: peer.last_health.map(|health| word::PeerHealth {
+            healthy: health.healthy,
+            error: health.error.unwrap_or_default(),
+            latency_us: health.latency.as_micros() as u64,
+            checked_at: health.checked_at,
+        }),
+    }
+}
+
+fn peer_status<SE: Error, CE: Error>(err: CoreError<SE, CE>) -> Status {
+    match err {
+        CoreError::PeerNotFound(id) => {
+            <GrpcInterfaceError as Into<Status>>::into(GrpcInterfaceError::NotFound(id))
+        }
+        CoreError::PeerAlreadyConnected(url) => {
+            <GrpcInterfaceError as Into<Status>>::into(GrpcInterfaceError::AlreadyExists(url))
+        }
+        CoreError::ClientError(err) => Status::unavailable(err.to_string()),
+        _ => GrpcInterfaceError::InternalServerError.into(),
+    }
+}
+
+fn chain_element(element: ChainElement) -> word::ChainElement {
+    word::ChainElement {
+        word: element.word,
+        provenance: element.provenance.map(|provenance| word::Provenance {
+            service_id: provenance.service_id,
+            hop_index: provenance.hop_index,
+            latency_us: provenance.latency.as_micros() as u64,
+        }),
+    }
+}
+
+fn chain_interruption(interruption: Option<Interruption>) -> word::ChainInterruption {
+    match interruption {
+        None => word::ChainInterruption::None,
+        Some(Interruption::DeadlineExceeded) => word::ChainInterruption::DeadlineExceeded,
+        Some(Interruption::PeerFailed) => word::ChainInterruption::PeerFailed,
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum GrpcInterfaceError {
     #[error("Error serving gRPC")]
@@ -22,6 +270,16 @@ pub enum GrpcInterfaceError {
     },
     #[error("Bad request: {0}")]
     BadRequest(String),
+    #[error("Peer {0} not found")]
+    NotFound(String),
+    #[error("Peer {0} is already connected")]
+    AlreadyExists(String),
+    #[error("Failed precondition: {0}")]
+    FailedPrecondition(String),
+    #[error("Deadline exceeded")]
+    DeadlineExceeded,
+    #[error("Aborted: {0}")]
+    Aborted(String),
     #[error("Internal server error")]
     InternalServerError,
 }
@@ -30,6 +288,15 @@ impl Into<Status> for GrpcInterfaceError {
     fn into(self) -> Status {
         match self {
             GrpcInterfaceError::BadRequest(msg) => Status::invalid_argument(msg),
+            GrpcInterfaceError::NotFound(id) => Status::not_found(format!("Peer {} not found", id)),
+            GrpcInterfaceError::AlreadyExists(url) => {
+                Status::already_exists(format!("Peer {} is already connected", url))
+            }
+            GrpcInterfaceError::FailedPrecondition(msg) => Status::failed_precondition(msg),
+            GrpcInterfaceError::DeadlineExceeded => {
+                Status::deadline_exceeded("The chain could not be completed before the deadline")
+            }
+            GrpcInterfaceError::Aborted(msg) => Status::aborted(msg),
             GrpcInterfaceError::InternalServerError => Status::internal("Internal error"),
             _ => Status::internal("Unknown error"),
         }
@@ -56,31 +323,376 @@ impl<S: Store, C: Client> WordService for GrpcInterface<S, C> {
     ) -> Result<Response<ChainResponse>, Status> {
         trace!("Received chain request: {:?}", request);
 
+        let state = chain_state(request);
+        let new_chain = self.core.chain(state).await.map_err(chain_status)?;
+        let interruption = chain_interruption(new_chain.interruption);
+        let failure_policy = word::FailurePolicy::from(new_chain.failure_policy);
+
+        Ok(Response::new(ChainResponse {
+            output: new_chain
+                .elements
+                .iter()
+                .map(|element| element.word.clone())
+                .collect(),
+            interruption: interruption.into(),
+            failure_policy: failure_policy.into(),
+            retries: new_chain.retries,
+            error: new_chain.error.unwrap_or_default(),
+            elements: new_chain.elements.into_iter().map(chain_element).collect(),
+        }))
+    }
+
+    type ChainStreamStream = BoxStream<'static, Result<ChainStreamResponse, Status>>;
+
+    #[tracing::instrument(fields(component = "Grpc Interface"), skip(self))]
+    async fn chain_stream(
+        &self,
+        request: Request<ChainRequest>,
+    ) -> Result<Response<Self::ChainStreamStream>, Status> {
+        trace!("Received chain_stream request: {:?}", request);
+
+        let state = chain_state(request);
+        // Status is the error type tonic expects from the items of a streaming response
+        #[allow(clippy::result_large_err)]
+        let events = self.core.chain_stream(state).map(|event| {
+            let event = match event.map_err(chain_status)? {
+                ChainEvent::Element(element) => {
+                    chain_stream_response::Event::Element(chain_element(element))
+                }
+                ChainEvent::Interrupted {
+                    interruption,
+                    error,
+                } => chain_stream_response::Event::Interrupted(word::ChainStreamInterruption {
+                    interruption: chain_interruption(Some(interruption)).into(),
+                    error: error.unwrap_or_default(),
+                }),
+            };
+            Ok(ChainStreamResponse { event: Some(event) })
+        });
+
+        Ok(Response::new(events.boxed()))
+    }
+
+    #[tracing::instrument(fields(component = "Grpc Interface"), skip(self))]
+    async fn next_word(
+        &self,
+        request: Request<NextWordRequest>,
+    ) -> Result<Response<NextWordResponse>, Status> {
+        trace!("Received next_word request: {:?}", request);
+
+        let deadline = grpc_timeout(request.metadata()).map(|timeout| Instant::now() + timeout);
+        let message = request.into_inner();
+        let state = ChainState {
+            options: ChainOptions {
+                strategy: message
+                    .strategy
+                    .map(|_| chain_strategy_kind(message.strategy())),
+                unique_words: message.unique_words,
+                ..Default::default()
+            },
+            chain: message.input,
+            visited_services: message.visited_services,
+            trace: message.trace.into_iter().map(Hop::from).collect(),
+            deadline,
+            ..Default::default()
+        };
+        let word = self.core.next_word(state).await.map_err(|e| match e {
+            CoreError::Empty => <GrpcInterfaceError as Into<Status>>::into(
+                GrpcInterfaceError::BadRequest("The store is empty".to_string()),
+            ),
+            CoreError::UnsatisfiableChain(msg) => <GrpcInterfaceError as Into<Status>>::into(
+                GrpcInterfaceError::FailedPrecondition(msg),
+            ),
+            CoreError::InvalidArgument(msg) => {
+                <GrpcInterfaceError as Into<Status>>::into(GrpcInterfaceError::BadRequest(msg))
+            }
+            CoreError::DeadlineExceeded => GrpcInterfaceError::DeadlineExceeded.into(),
+            _ => GrpcInterfaceError::InternalServerError.into(),
+        })?;
+
+        Ok(Response::new(NextWordResponse { word }))
+    }
+
+    async fn health(
+        &self,
+        request: Request<HealthRequest>,
+    ) -> Result<Response<HealthResponse>, Status> {
+        trace!("Received health request: {:?}", request);
+        Ok(Response::new(HealthResponse {
+            service_id: self.core.service_id().to_string(),
+        }))
+    }
+
+    #[tracing::instrument(fields(component = "Grpc Interface"), skip(self))]
+    async fn list_words(
+        &self,
+        request: Request<ListWordsRequest>,
+    ) -> Result<Response<ListWordsResponse>, Status> {
+        trace!("Received list_words request: {:?}", request);
+
         let message = request.into_inner();
-        let new_chain = self
+        let limit = match message.limit {
+            0 => DEFAULT_LIST_LIMIT,
+            limit => limit as usize,
+        };
+        let page = self
             .core
-            .chain(message.input, message.count)
+            .list_words(
+                Some(message.cursor).filter(|cursor| !cursor.is_empty()),
+                limit,
+                Some(message.prefix).filter(|prefix| !prefix.is_empty()),
+            )
+            .await
+            .map_err(|_| {
+                <GrpcInterfaceError as Into<Status>>::into(GrpcInterfaceError::InternalServerError)
+            })?;
+
+        Ok(Response::new(ListWordsResponse {
+            words: page.words,
+            next_cursor: page.next_cursor.unwrap_or_default(),
+        }))
+    }
+
+    #[tracing::instrument(fields(component = "Grpc Interface"), skip(self))]
+    async fn random_word(
+        &self,
+        request: Request<RandomWordRequest>,
+    ) -> Result<Response<RandomWordResponse>, Status> {
+        trace!("Received random_word request: {:?}", request);
+
+        let word = self.core.random_word().await.map_err(|e| match e {
+            CoreError::Empty => <GrpcInterfaceError as Into<Status>>::into(
+                GrpcInterfaceError::BadRequest("The store is empty".to_string()),
+            ),
+            _ => GrpcInterfaceError::InternalServerError.into(),
+        })?;
+
+        Ok(Response::new(RandomWordResponse { word }))
+    }
+
+    #[tracing::instrument(fields(component = "Grpc Interface"), skip(self))]
+    async fn set_weight(
+        &self,
+        request: Request<SetWeightRequest>,
+    ) -> Result<Response<SetWeightResponse>, Status> {
+        trace!("Received set_weight request: {:?}", request);
+
+        let message = request.into_inner();
+        self.core
+            .clone()
+            .set_weight(message.word, message.weight)
             .await
             .map_err(|e| match e {
-                CoreError::NoConnectedServices => {
-                    <GrpcInterfaceError as Into<Status>>::into(GrpcInterfaceError::BadRequest(
-                        "This service is not connected to an example-service".to_string(),
-                    ))
+                CoreError::NotFound(word) => Status::not_found(format!("Word {} not found", word)),
+                CoreError::InvalidArgument(msg) => {
+                    <GrpcInterfaceError as Into<Status>>::into(GrpcInterfaceError::BadRequest(msg))
                 }
-                CoreError::Empty => <GrpcInterfaceError as Into<Status>>::into(
-                    GrpcInterfaceError::BadRequest("The store is empty".to_string()),
-                ),
                 _ => GrpcInterfaceError::InternalServerError.into(),
             })?;
 
-        Ok(Response::new(ChainResponse { output: new_chain }))
+        Ok(Response::new(SetWeightResponse {}))
     }
 
-    async fn health(
+    #[tracing::instrument(fields(component = "Grpc Interface"), skip(self))]
+    async fn learn_sentence(
         &self,
-        request: Request<HealthRequest>,
-    ) -> Result<Response<HealthResponse>, Status> {
-        trace!("Received health request: {:?}", request);
-        Ok(Response::new(HealthResponse {}))
+        request: Request<LearnSentenceRequest>,
+    ) -> Result<Response<LearnSentenceResponse>, Status> {
+        trace!("Received learn_sentence request: {:?}", request);
+
+        let message = request.into_inner();
+        self.core
+            .clone()
+            .learn_sentence(message.sentence)
+            .await
+            .map_err(|e| match e {
+                CoreError::InvalidArgument(msg) => {
+                    <GrpcInterfaceError as Into<Status>>::into(GrpcInterfaceError::BadRequest(msg))
+                }
+                _ => GrpcInterfaceError::InternalServerError.into(),
+            })?;
+
+        Ok(Response::new(LearnSentenceResponse {}))
+    }
+
+    async fn chain_limits(
+        &self,
+        request: Request<ChainLimitsRequest>,
+    ) -> Result<Response<ChainLimitsResponse>, Status> {
+        trace!("Received chain_limits request: {:?}", request);
+        let limits = self.core.limits();
+        Ok(Response::new(ChainLimitsResponse {
+            max_chain_count: limits.max_chain_count,
+            max_input_length: limits.max_input_length as u64,
+            max_word_length: limits.max_word_length as u64,
+        }))
+    }
+}
+
+#[tonic::async_trait]
+impl<S: Store, C: Client> AdminService for GrpcInterface<S, C> {
+    #[tracing::instrument(fields(component = "Grpc Interface"), skip(self))]
+    async fn list_peers(
+        &self,
+        request: Request<ListPeersRequest>,
+    ) -> Result<Response<ListPeersResponse>, Status> {
+        trace!("Received list_peers request: {:?}", request);
+        let peers = self.core.peers().await;
+        Ok(Response::new(ListPeersResponse {
+            peers: peers.into_iter().map(peer).collect(),
+        }))
+    }
+
+    #[tracing::instrument(fields(component = "Grpc Interface"), skip(self))]
+    async fn add_peer(
+        &self,
+        request: Request<AddPeerRequest>,
+    ) -> Result<Response<AddPeerResponse>, Status> {
+        trace!("Received add_peer request: {:?}", request);
+        let added = self
+            .core
+            .add_peer(request.into_inner().url)
+            .await
+            .map_err(peer_status)?;
+        Ok(Response::new(AddPeerResponse {
+            peer: Some(peer(added)),
+        }))
+    }
+
+    #[tracing::instrument(fields(component = "Grpc Interface"), skip(self))]
+    async fn remove_peer(
+        &self,
+        request: Request<RemovePeerRequest>,
+    ) -> Result<Response<RemovePeerResponse>, Status> {
+        trace!("Received remove_peer request: {:?}", request);
+        self.core
+            .remove_peer(&request.into_inner().id)
+            .await
+            .map_err(peer_status)?;
+        Ok(Response::new(RemovePeerResponse {}))
+    }
+}
+
+/// Serves the membership protocol, apart from the words which go through the core.
+#[derive(Debug)]
+pub struct MembershipInterface {
+    membership: Membership,
+}
+
+impl MembershipInterface {
+    pub fn new(membership: Membership) -> Self {
+        MembershipInterface { membership }
+    }
+}
+
+#[tonic::async_trait]
+impl MembershipService for MembershipInterface {
+    async fn gossip(
+        &self,
+        request: Request<GossipRequest>,
+    ) -> Result<Response<GossipResponse>, Status> {
+        trace!("Received gossip request: {:?}", request);
+        let members = request.into_inner().members;
+        self.membership
+            .merge(members.into_iter().map(Member::from).collect())
+            .await;
+        Ok(Response::new(GossipResponse {
+            members: self
+                .membership
+                .members()
+                .await
+                .into_iter()
+                .map(Member::into)
+                .collect(),
+        }))
+    }
+
+    async fn indirect_ping(
+        &self,
+        request: Request<IndirectPingRequest>,
+    ) -> Result<Response<IndirectPingResponse>, Status> {
+        trace!("Received indirect_ping request: {:?}", request);
+        let message = request.into_inner();
+        let acked = self
+            .membership
+            .indirect_ping(
+                &message.url,
+                message.members.into_iter().map(Member::from).collect(),
+            )
+            .await;
+        Ok(Response::new(IndirectPingResponse {
+            acked,
+            members: self
+                .membership
+                .members()
+                .await
+                .into_iter()
+                .map(Member::into)
+                .collect(),
+        }))
+    }
+}
+
+/// Service whose status only reflects this process answering. Peers check it rather than the
+/// readiness of the word service, which would never recover if instances required each other.
+pub const LIVENESS_SERVICE: &str = "liveness";
+
+/// Keeps the statuses served by `grpc.health.v1.Health` up to date: the whole server and the
+/// word service follow the readiness of `core`, while liveness and the admin service stay
+/// serving as long as the process runs.
+pub async fn report_health<S: Store, C: Client>(
+    core: Core<S, C>,
+    reporter: HealthReporter,
+    interval: Duration,
+) {
+    reporter
+        .set_service_status(LIVENESS_SERVICE, ServingStatus::Serving)
+        .await;
+    reporter
+        .set_serving::<AdminServiceServer<GrpcInterface<S, C>>>()
+        .await;
+
+    let mut ticker = tokio::time::interval(interval);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let mut last_status = None;
+    loop {
+        ticker.tick().await;
+        let status = match core.ready_check().await {
+            Ok(()) => ServingStatus::Serving,
+            Err(_) => ServingStatus::NotServing,
+        };
+        // Watchers are notified of every status set, changed or not
+        if last_status == Some(status) {
+            continue;
+        }
+        info!("Word service is now {0}", status);
+        reporter.set_service_status("", status).await;
+        reporter
+            .set_service_status(
+                <WordServiceServer<GrpcInterface<S, C>> as NamedService>::NAME,
+                status,
+            )
+            .await;
+        last_status = Some(status);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grpc_timeout_sets_the_chain_deadline() {
+        let mut request = Request::new(ChainRequest::default());
+        request.set_timeout(Duration::from_millis(1500));
+        assert_eq!(
+            grpc_timeout(request.metadata()),
+            Some(Duration::from_millis(1500))
+        );
+
+        let before = Instant::now();
+        let deadline = chain_state(request).deadline.unwrap();
+        assert!(deadline >= before + Duration::from_millis(1500));
+        assert!(deadline <= Instant::now() + Duration::from_millis(1500));
     }
 }
@@ -1,17 +1,44 @@
 use crate::clients::Client;
-use crate::core::{Core, CoreError};
-use crate::stores::Store;
+use crate::core::{
+    check_weight, check_word, ChainElement, ChainEvent, ChainLimits, ChainMode, ChainOptions,
+    ChainState, Core, CoreError, FailurePolicy, HealthDetails, Interruption, Peer, PeerState,
+};
+use crate::stores::{AddWordStatus, Store, WordMetadata, WordRecord};
+use crate::strategies::ChainStrategyKind;
 use axum::{
-    extract::{Path, State},
-    http::StatusCode,
-    routing::{get, post},
+    body::{Body, Bytes},
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+    routing::{delete, get, post, put},
     Json, Router,
 };
 use axum_tracing_opentelemetry::middleware::{OtelAxumLayer, OtelInResponseLayer};
+use futures::stream::{self, BoxStream, StreamExt, TryStreamExt};
 use serde::{Deserialize, Serialize};
+use std::convert::Infallible;
+use std::time::{Duration, Instant};
 use thiserror::Error;
+use tokio_util::bytes::BytesMut;
+use tokio_util::codec::{Decoder, FramedRead, LinesCodec, LinesCodecError};
+use tokio_util::io::StreamReader;
 use tower_http::trace::TraceLayer;
-use tracing::{info, trace, warn};
+use tracing::{error, info, trace, warn};
+
+/// Number of words listed when the request does not set a limit.
+const DEFAULT_LIST_LIMIT: usize = 100;
+
+/// Header carrying the time allowed to build a chain, in milliseconds.
+const CHAIN_TIMEOUT_HEADER: &str = "x-chain-timeout-ms";
+
+/// Maximum number of words sent to the store at once when importing.
+const IMPORT_BATCH_SIZE: usize = 500;
+
+/// Maximum length of an imported line in bytes, longer lines being rejected.
+const MAX_IMPORT_LINE_LENGTH: usize = 64 * 1024;
 
 #[derive(Error, Debug)]
 pub enum HttpInterfaceError {
@@ -31,10 +58,20 @@ pub enum HttpInterfaceError {
     NotFound(String),
     #[error("Word {0} already exists")]
     Conflict(String),
+    #[error("Peer {0} not found")]
+    PeerNotFound(String),
+    #[error("Peer {0} is already connected")]
+    PeerAlreadyConnected(String),
     #[error("Bad request: {0}")]
     BadRequest(String),
     #[error("Service unavailable")]
     ServiceUnavailable,
+    #[error("Bad gateway: {0}")]
+    BadGateway(String),
+    #[error("Gateway timeout")]
+    GatewayTimeout,
+    #[error("Unsupported media type {0}")]
+    UnsupportedMediaType(String),
     #[error("Internal server error")]
     InternalServerError,
 }
@@ -47,6 +84,11 @@ impl Into<(StatusCode, String)> for HttpInterfaceError {
                 StatusCode::CONFLICT,
                 format!("Word '{}' already exists", word),
             ),
+            Self::PeerNotFound(id) => (StatusCode::NOT_FOUND, format!("Peer '{}' not found", id)),
+            Self::PeerAlreadyConnected(url) => (
+                StatusCode::CONFLICT,
+                format!("Peer '{}' is already connected", url),
+            ),
             Self::InternalServerError => (
                 StatusCode::INTERNAL_SERVER_ERROR,
                 "Internal server error".to_string(),
@@ -56,6 +98,15 @@ impl Into<(StatusCode, String)> for HttpInterfaceError {
                 StatusCode::SERVICE_UNAVAILABLE,
                 "Service unavailable".to_string(),
             ),
+            Self::BadGateway(msg) => (StatusCode::BAD_GATEWAY, msg),
+            Self::GatewayTimeout => (
+                StatusCode::GATEWAY_TIMEOUT,
+                "The chain could not be completed before the deadline".to_string(),
+            ),
+            Self::UnsupportedMediaType(media_type) => (
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                format!("Unsupported media type '{}'", media_type),
+            ),
             _ => (
                 StatusCode::INTERNAL_SERVER_ERROR,
                 "Unknown error".to_string(),
@@ -64,6 +115,73 @@ impl Into<(StatusCode, String)> for HttpInterfaceError {
     }
 }
 
+type EventStream = BoxStream<'static, Result<Event, Infallible>>;
+
+/// Reads the time allowed to build a chain from the `x-chain-timeout-ms` header
+/// and the request, the tightest of both applying.
+fn chain_deadline(
+    headers: &HeaderMap,
+    timeout_ms: Option<u64>,
+) -> Result<Option<Instant>, (StatusCode, String)> {
+    let header_timeout = match headers.get(CHAIN_TIMEOUT_HEADER) {
+        Some(value) => Some(
+            value
+                .to_str()
+                .ok()
+                .and_then(|value| value.parse::<u64>().ok())
+                .ok_or_else(|| {
+                    <HttpInterfaceError as Into<(StatusCode, String)>>::into(
+                        HttpInterfaceError::BadRequest(format!(
+                            "Header {} must be a number of milliseconds",
+                            CHAIN_TIMEOUT_HEADER
+                        )),
+                    )
+                })?,
+        ),
+        None => None,
+    };
+
+    Ok(header_timeout
+        .into_iter()
+        .chain(timeout_ms)
+        .min()
+        .map(|timeout| Instant::now() + Duration::from_millis(timeout)))
+}
+
+fn chain_error<SE: std::error::Error, CE: std::error::Error>(
+    err: CoreError<SE, CE>,
+) -> (StatusCode, String) {
+    match err {
+        CoreError::NoConnectedServices => {
+            warn!("An attempt to chain was called but service is not connected to another example-service");
+            HttpInterfaceError::BadRequest(
+                "This service is not connected to another example-service".to_string(),
+            )
+            .into()
+        }
+        CoreError::Empty => HttpInterfaceError::BadRequest("The store is empty".to_string()).into(),
+        CoreError::UnsatisfiableChain(msg) => HttpInterfaceError::BadRequest(msg).into(),
+        CoreError::InvalidArgument(msg) => HttpInterfaceError::BadRequest(msg).into(),
+        CoreError::DeadlineExceeded => HttpInterfaceError::GatewayTimeout.into(),
+        CoreError::HopFailed(msg) => HttpInterfaceError::BadGateway(msg).into(),
+        CoreError::ClientError(err) => HttpInterfaceError::BadGateway(err.to_string()).into(),
+        _ => HttpInterfaceError::InternalServerError.into(),
+    }
+}
+
+fn peer_error<SE: std::error::Error, CE: std::error::Error>(
+    err: CoreError<SE, CE>,
+) -> (StatusCode, String) {
+    match err {
+        CoreError::PeerNotFound(id) => HttpInterfaceError::PeerNotFound(id).into(),
+        CoreError::PeerAlreadyConnected(url) => {
+            HttpInterfaceError::PeerAlreadyConnected(url).into()
+        }
+        CoreError::ClientError(err) => HttpInterfaceError::BadGateway(err.to_string()).into(),
+        _ => HttpInterfaceError::InternalServerError.into(),
+    }
+}
+
 pub struct HttpInterface<S: Store, C: Client> {
     core: Core<S, C>,
 }
@@ -92,11 +210,25 @@ impl<S: Store, C: Client> HttpInterface<S, C> {
 
     fn create_app(&self) -> Router {
         Router::new()
-            .route("/word", post(Self::add_word).delete(Self::remove_word))
+            .route(
+                "/word",
+                get(Self::list_words)
+                    .post(Self::add_word)
+                    .delete(Self::remove_word),
+            )
             .route("/word/{word}", get(Self::get_word))
+            .route("/word/{word}/weight", put(Self::set_weight))
             .route("/word/random", post(Self::random_word))
             .route("/word/chain", post(Self::start_chain))
+            .route("/word/chain/limits", get(Self::chain_limits))
+            .route("/word/chain/stream", get(Self::stream_chain))
+            .route("/sentences", post(Self::learn_sentence))
+            .route("/words:import", post(Self::import_words))
+            .route("/words:export", get(Self::export_words))
+            .route("/peers", get(Self::list_peers).post(Self::add_peer))
+            .route("/peers/{id}", delete(Self::remove_peer))
             .route("/health", get(Self::health_check))
+            .route("/health/details", get(Self::health_details))
             .route("/ready", get(Self::ready_check))
             .with_state(self.core.clone())
             .layer(TraceLayer::new_for_http())
@@ -104,19 +236,62 @@ impl<S: Store, C: Client> HttpInterface<S, C> {
             .layer(OtelAxumLayer::default())
     }
 
-    async fn health_check(State(state): State<Core<S, C>>) -> Result<(), (StatusCode, String)> {
-        state.health_check().await.map_err(|err| match err {
-            CoreError::ServiceUnavailable => HttpInterfaceError::ServiceUnavailable.into(),
-            _ => HttpInterfaceError::InternalServerError.into(),
-        })
-    }
+    /// Liveness only reflects this process answering, the store and peers being left to readiness
+    /// so that one unhealthy instance does not get all of them restarted.
+    async fn health_check() {}
 
     async fn ready_check(State(state): State<Core<S, C>>) -> Result<(), (StatusCode, String)> {
         state.ready_check().await.map_err(|err| match err {
+            CoreError::ServiceUnavailable => HttpInterfaceError::ServiceUnavailable.into(),
             _ => HttpInterfaceError::InternalServerError.into(),
         })
     }
 
+    /// Answers with 503 along with the report when the service is not ready.
+    async fn health_details(
+        State(state): State<Core<S, C>>,
+    ) -> (StatusCode, Json<HealthDetailsResponse>) {
+        let details = state.health_details().await;
+        let status = match details.ready {
+            true => StatusCode::OK,
+            false => StatusCode::SERVICE_UNAVAILABLE,
+        };
+        (status, Json(details.into()))
+    }
+
+    #[tracing::instrument(fields(component = "Http Interface"), skip(state))]
+    async fn list_peers(State(state): State<Core<S, C>>) -> Json<Vec<PeerResponse>> {
+        trace!("Received list_peers request");
+        Json(state.peers().await.into_iter().map(Into::into).collect())
+    }
+
+    #[tracing::instrument(fields(component = "Http Interface"), skip(state))]
+    async fn add_peer(
+        State(state): State<Core<S, C>>,
+        Json(payload): Json<AddPeerRequest>,
+    ) -> Result<(StatusCode, Json<PeerResponse>), (StatusCode, String)> {
+        trace!("Received add_peer request for url: {}", payload.url);
+        state
+            .add_peer(payload.url)
+            .await
+            .map_err(peer_error)
+            .map(|peer| (StatusCode::CREATED, Json(peer.into())))
+    }
+
+    /// Drops a peer, `id` being either its service id or its url.
+    #[tracing::instrument(fields(component = "Http Interface"), skip(state))]
+    async fn remove_peer(
+        State(state): State<Core<S, C>>,
+        Path(id): Path<String>,
+    ) -> Result<StatusCode, (StatusCode, String)> {
+        trace!("Received remove_peer request for peer: {}", id);
+        state
+            .remove_peer(&id)
+            .await
+            .map_err(peer_error)
+            .map(|_| StatusCode::NO_CONTENT)
+    }
+
     #[tracing::instrument(fields(component = "Http Interface"), skip(state))]
     async fn add_word(
         State(mut state): State<Core<S, C>>,
@@ -124,15 +299,34 @@ impl<S: Store, C: Client> HttpInterface<S, C> {
     ) -> Result<StatusCode, (StatusCode, String)> {
         trace!("Received add_word request for word: {}", payload.word);
         state
-            .add_word(payload.word.clone())
+            .add_word(payload.word.clone(), payload.metadata.clone())
             .await
             .map_err(|err| match err {
                 CoreError::AlreadyExists(word) => HttpInterfaceError::Conflict(word).into(),
+                CoreError::InvalidArgument(msg) => HttpInterfaceError::BadRequest(msg).into(),
                 _ => HttpInterfaceError::InternalServerError.into(),
             })
             .map(|_| StatusCode::CREATED)
     }
 
+    #[tracing::instrument(fields(component = "Http Interface"), skip(state))]
+    async fn set_weight(
+        State(mut state): State<Core<S, C>>,
+        Path(word): Path<String>,
+        Json(payload): Json<SetWeightRequest>,
+    ) -> Result<StatusCode, (StatusCode, String)> {
+        trace!("Received set_weight request for word: {}", word);
+        state
+            .set_weight(word, payload.weight)
+            .await
+            .map_err(|err| match err {
+                CoreError::NotFound(word) => HttpInterfaceError::NotFound(word).into(),
+                CoreError::InvalidArgument(msg) => HttpInterfaceError::BadRequest(msg).into(),
+                _ => HttpInterfaceError::InternalServerError.into(),
+            })
+            .map(|_| StatusCode::OK)
+    }
+
     #[tracing::instrument(fields(component = "Http Interface"), skip(state))]
     async fn get_word(
         State(state): State<Core<S, C>>,
@@ -146,7 +340,32 @@ impl<S: Store, C: Client> HttpInterface<S, C> {
                 CoreError::NotFound(word) => HttpInterfaceError::NotFound(word).into(),
                 _ => HttpInterfaceError::InternalServerError.into(),
             })
-            .map(|word| (StatusCode::OK, Json(GetWordResponse { word })))
+            .map(|record| (StatusCode::OK, Json(GetWordResponse { record })))
+    }
+
+    #[tracing::instrument(fields(component = "Http Interface"), skip(state))]
+    async fn list_words(
+        State(state): State<Core<S, C>>,
+        Query(query): Query<ListWordsQuery>,
+    ) -> Result<(StatusCode, Json<ListWordsResponse>), (StatusCode, String)> {
+        trace!("Received list_words request: {:?}", query);
+        state
+            .list_words(
+                query.cursor.filter(|cursor| !cursor.is_empty()),
+                query.limit.unwrap_or(DEFAULT_LIST_LIMIT),
+                query.prefix.filter(|prefix| !prefix.is_empty()),
+            )
+            .await
+            .map_err(|_| HttpInterfaceError::InternalServerError.into())
+            .map(|page| {
+                (
+                    StatusCode::OK,
+                    Json(ListWordsResponse {
+                        words: page.words,
+                        next_cursor: page.next_cursor,
+                    }),
+                )
+            })
     }
 
     #[tracing::instrument(fields(component = "Http Interface"), skip(state))]
@@ -188,24 +407,267 @@ impl<S: Store, C: Client> HttpInterface<S, C> {
     #[tracing::instrument(fields(component = "Http Interface"), skip(state))]
     async fn start_chain(
         State(state): State<Core<S, C>>,
+        headers: HeaderMap,
         Json(payload): Json<ChainRequest>,
     ) -> Result<(StatusCode, Json<ChainResponse>), (StatusCode, String)> {
         trace!("Received chain request");
+        let deadline = chain_deadline(&headers, payload.timeout_ms)?;
+
         // Implement the logic to generate a chain of words based on the inputs and count
         state
-            .chain(payload.input, payload.count)
+            .chain(ChainState {
+                chain: payload.input,
+                count: payload.count,
+                options: payload.options,
+                deadline,
+                ..Default::default()
+            })
             .await
-            .map_err(|err| match err {
-                CoreError::NoConnectedServices => {
-                    warn!("An attempt to chain was called but service is not connected to another example-service");
-                    HttpInterfaceError::BadRequest(
-                        "This service is not connected to another example-service".to_string(),
-                    ).into()
+            .map_err(chain_error)
+            .map(|chain| {
+                Ok((
+                    StatusCode::OK,
+                    Json(ChainResponse {
+                        outputs: chain
+                            .elements
+                            .iter()
+                            .map(|element| element.word.clone())
+                            .collect(),
+                        elements: chain
+                            .elements
+                            .into_iter()
+                            .map(ChainElementResponse::from)
+                            .collect(),
+                        interrupted: chain.interruption,
+                        error: chain.error,
+                        failure_policy: chain.failure_policy,
+                        retries: chain.retries,
+                    }),
+                ))
+            })?
+    }
+
+    /// Streams the words of a chain as server-sent events, a `word` event being
+    /// sent as soon as each hop picks its word.
+    #[tracing::instrument(fields(component = "Http Interface"), skip(state))]
+    async fn stream_chain(
+        State(state): State<Core<S, C>>,
+        headers: HeaderMap,
+        Query(query): Query<ChainStreamQuery>,
+    ) -> Result<Sse<EventStream>, (StatusCode, String)> {
+        trace!("Received chain stream request");
+        let deadline = chain_deadline(&headers, query.timeout_ms)?;
+
+        let events = state
+            .chain_stream(ChainState {
+                chain: query.input.split_whitespace().map(str::to_string).collect(),
+                count: query.count,
+                options: ChainOptions {
+                    strategy: query.strategy,
+                    avoid_revisiting_services: query.avoid_revisiting_services,
+                    unique_words: query.unique_words,
+                    allow_partial: query.allow_partial,
+                    mode: query.mode,
+                    failure_policy: query.failure_policy,
                 },
-                CoreError::Empty => HttpInterfaceError::BadRequest("This service is not connected to another example-service".to_string()).into(),
+                deadline,
+                ..Default::default()
+            })
+            .map(|event| {
+                let event =
+                    match event {
+                        Ok(ChainEvent::Element(element)) => Event::default()
+                            .event("word")
+                            .json_data(ChainElementResponse::from(element)),
+                        Ok(ChainEvent::Interrupted {
+                            interruption,
+                            error,
+                        }) => Event::default().event("interrupted").json_data(
+                            ChainInterruptionResponse {
+                                interrupted: interruption,
+                                error,
+                            },
+                        ),
+                        Err(err) => {
+                            let (status, message) = chain_error(err);
+                            Event::default()
+                                .event("error")
+                                .json_data(ChainErrorResponse {
+                                    status: status.as_u16(),
+                                    message,
+                                })
+                        }
+                    };
+                Ok(event.unwrap_or_else(|err| {
+                    error!("Error serializing chain event: {:?}", err);
+                    Event::default().event("error")
+                }))
+            })
+            .boxed();
+
+        Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+    }
+
+    async fn chain_limits(State(state): State<Core<S, C>>) -> Json<ChainLimits> {
+        Json(*state.limits())
+    }
+
+    #[tracing::instrument(fields(component = "Http Interface"), skip(state))]
+    async fn learn_sentence(
+        State(mut state): State<Core<S, C>>,
+        Json(payload): Json<LearnSentenceRequest>,
+    ) -> Result<StatusCode, (StatusCode, String)> {
+        trace!("Received learn_sentence request");
+        state
+            .learn_sentence(payload.sentence)
+            .await
+            .map_err(|err| match err {
+                CoreError::InvalidArgument(msg) => HttpInterfaceError::BadRequest(msg).into(),
                 _ => HttpInterfaceError::InternalServerError.into(),
             })
-            .map(|chain| Ok((StatusCode::OK, Json(ChainResponse { outputs: chain }))))?
+            .map(|_| StatusCode::CREATED)
+    }
+
+    #[tracing::instrument(fields(component = "Http Interface"), skip(state, body))]
+    async fn import_words(
+        State(state): State<Core<S, C>>,
+        headers: HeaderMap,
+        body: Body,
+    ) -> Result<Response, (StatusCode, String)> {
+        let (format, header_lines) = match headers.get(header::CONTENT_TYPE) {
+            Some(content_type) => {
+                let content_type = content_type.to_str().unwrap_or_default();
+                let format = WordFormat::from_media_type(content_type).ok_or_else(|| {
+                    HttpInterfaceError::UnsupportedMediaType(content_type.to_string()).into()
+                })?;
+                (format, usize::from(has_header(content_type)))
+            }
+            None => (WordFormat::Text, 0),
+        };
+        trace!("Received import_words request with format {:?}", format);
+
+        let lines = FramedRead::new(
+            StreamReader::new(body.into_data_stream().map_err(std::io::Error::other)),
+            ImportLineCodec(LinesCodec::new_with_max_length(MAX_IMPORT_LINE_LENGTH)),
+        )
+        .enumerate()
+        .map(|(index, line)| (index + 1, line));
+
+        let results = lines
+            .filter_map(move |(line_number, line)| async move {
+                match line {
+                    Ok(_) if line_number <= header_lines => None,
+                    Ok(Some(line)) => format.parse_line(&line).map(|parsed| {
+                        parsed
+                            .and_then(Self::check_import)
+                            .map_err(|error| ImportResult::Invalid {
+                                line: line_number,
+                                error,
+                            })
+                    }),
+                    Ok(None) => Some(Err(ImportResult::Invalid {
+                        line: line_number,
+                        error: format!("Line must have at most {} bytes", MAX_IMPORT_LINE_LENGTH),
+                    })),
+                    Err(e) => {
+                        warn!("Error reading import body: {:?}", e);
+                        Some(Err(ImportResult::Failed {
+                            error: "Failed to read request body".to_string(),
+                        }))
+                    }
+                }
+            })
+            .ready_chunks(IMPORT_BATCH_SIZE)
+            .then(move |batch| {
+                let mut state = state.clone();
+                async move { Self::import_batch(&mut state, batch).await }
+            })
+            .flat_map(stream::iter)
+            .scan(false, |failed, result| {
+                if *failed {
+                    return futures::future::ready(None);
+                }
+                *failed = matches!(result, ImportResult::Failed { .. });
+                futures::future::ready(Some(result))
+            })
+            .map(|result| {
+                let mut line = serde_json::to_vec(&result)?;
+                line.push(b'\n');
+                Ok::<_, serde_json::Error>(Bytes::from(line))
+            });
+
+        Ok((
+            [(header::CONTENT_TYPE, WordFormat::Ndjson.media_type())],
+            Body::from_stream(results),
+        )
+            .into_response())
+    }
+
+    /// Rejects the words the core would reject, so that they do not fail their whole batch.
+    fn check_import(
+        (word, metadata): (String, WordMetadata),
+    ) -> Result<(String, WordMetadata), String> {
+        match check_word::<S::E, C::E>(&word).and_then(|()| check_weight(metadata.weight)) {
+            Ok(()) => Ok((word, metadata)),
+            Err(CoreError::InvalidArgument(msg)) => Err(msg),
+            Err(err) => Err(err.to_string()),
+        }
+    }
+
+    async fn import_batch(
+        state: &mut Core<S, C>,
+        batch: Vec<Result<(String, WordMetadata), ImportResult>>,
+    ) -> Vec<ImportResult> {
+        let words: Vec<(String, WordMetadata)> = batch
+            .iter()
+            .filter_map(|item| item.as_ref().ok().cloned())
+            .collect();
+
+        let mut statuses = match state.add_words(words).await {
+            Ok(statuses) => statuses.into_iter(),
+            Err(err) => {
+                error!("Error importing batch of words: {:?}", err);
+                return vec![ImportResult::Failed {
+                    error: "Internal server error".to_string(),
+                }];
+            }
+        };
+
+        batch
+            .into_iter()
+            .map(|item| match item {
+                Ok((word, _)) => match statuses.next() {
+                    Some(AddWordStatus::Created) => ImportResult::Created { word },
+                    _ => ImportResult::AlreadyExists { word },
+                },
+                Err(result) => result,
+            })
+            .collect()
+    }
+
+    #[tracing::instrument(fields(component = "Http Interface"), skip(state))]
+    async fn export_words(
+        State(state): State<Core<S, C>>,
+        Query(query): Query<ExportQuery>,
+    ) -> Result<Response, (StatusCode, String)> {
+        let format = query.format.unwrap_or(WordFormat::Ndjson);
+        trace!("Received export_words request with format {:?}", format);
+
+        let header_line = match format {
+            WordFormat::Csv => Some(Ok(Bytes::from_static(b"word\n"))),
+            _ => None,
+        };
+        let lines = state.iter_words().map(move |result| {
+            result
+                .map(|record| Bytes::from(format.format_record(&record)))
+                .map_err(|_| std::io::Error::other("Failed to read words from the store"))
+        });
+
+        Ok((
+            [(header::CONTENT_TYPE, format.media_type())],
+            Body::from_stream(stream::iter(header_line).chain(lines)),
+        )
+            .into_response())
     }
 }
 
@@ -213,11 +675,162 @@ impl<S: Store, C: Client> HttpInterface<S, C> {
 struct ChainRequest {
     pub input: Vec<String>,
     pub count: u32,
+    /// Time allowed to build the chain, the `x-chain-timeout-ms` header taking precedence when lower
+    pub timeout_ms: Option<u64>,
+    #[serde(flatten)]
+    pub options: ChainOptions,
+}
+
+/// Query of a chain stream, the input being a whitespace-separated list of words.
+#[derive(Deserialize, Debug)]
+struct ChainStreamQuery {
+    pub input: String,
+    pub count: u32,
+    pub timeout_ms: Option<u64>,
+    pub strategy: Option<ChainStrategyKind>,
+    #[serde(default)]
+    pub avoid_revisiting_services: bool,
+    #[serde(default)]
+    pub unique_words: bool,
+    #[serde(default)]
+    pub allow_partial: bool,
+    #[serde(default)]
+    pub mode: ChainMode,
+    pub failure_policy: Option<FailurePolicy>,
+}
+
+#[derive(Serialize, Debug)]
+struct ChainInterruptionResponse {
+    pub interrupted: Interruption,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+struct ChainErrorResponse {
+    pub status: u16,
+    pub message: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct LearnSentenceRequest {
+    pub sentence: String,
 }
 
 #[derive(Serialize, Debug)]
 struct ChainResponse {
     pub outputs: Vec<String>,
+    /// Same words as `outputs`, along with their provenance
+    pub elements: Vec<ChainElementResponse>,
+    /// Why the chain is shorter than requested, if it was cut short
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interrupted: Option<Interruption>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub failure_policy: FailurePolicy,
+    /// Number of hops sent again to another peer after a failure
+    pub retries: u32,
+}
+
+#[derive(Serialize, Debug)]
+struct ChainElementResponse {
+    pub word: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub service_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hop_index: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latency_us: Option<u64>,
+}
+
+impl From<ChainElement> for ChainElementResponse {
+    fn from(element: ChainElement) -> Self {
+        let provenance = element.provenance;
+        ChainElementResponse {
+            word: element.word,
+            service_id: provenance.as_ref().map(|p| p.service_id.clone()),
+            hop_index: provenance.as_ref().map(|p| p.hop_index),
+            latency_us: provenance.map(|p| p.latency.as_micros() as u64),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct AddPeerRequest {
+    pub url: String,
+}
+
+#[derive(Serialize, Debug)]
+struct PeerResponse {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub url: String,
+    pub state: PeerState,
+    pub failed_connection_attempts: u32,
+    pub consecutive_failures: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_health: Option<PeerHealthResponse>,
+}
+
+#[derive(Serialize, Debug)]
+struct PeerHealthResponse {
+    pub healthy: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub latency_us: u64,
+    /// Milliseconds since the Unix epoch
+    pub checked_at: u64,
+}
+
+impl From<Peer> for PeerResponse {
+    fn from(peer: Peer) -> Self {
+        PeerResponse {
+            id: peer.id,
+            url: peer.url,
+            state: peer.state,
+            failed_connection_attempts: peer.failed_connection_attempts,
+            consecutive_failures: peer.consecutive_failures,
+            last_health: peer.last_health.map(|health| PeerHealthResponse {
+                healthy: health.healthy,
+                error: health.error,
+                latency_us: health.latency.as_micros() as u64,
+                checked_at: health.checked_at,
+            }),
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+struct HealthDetailsResponse {
+    pub ready: bool,
+    pub store: StoreHealthResponse,
+    pub healthy_peers: usize,
+    pub min_healthy_peers: usize,
+    pub peers: Vec<PeerResponse>,
+}
+
+#[derive(Serialize, Debug)]
+struct StoreHealthResponse {
+    pub healthy: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub latency_us: u64,
+}
+
+impl From<HealthDetails> for HealthDetailsResponse {
+    fn from(details: HealthDetails) -> Self {
+        HealthDetailsResponse {
+            ready: details.ready,
+            store: StoreHealthResponse {
+                healthy: details.store.healthy,
+                error: details.store.error,
+                latency_us: details.store.latency.as_micros() as u64,
+            },
+            healthy_peers: details.healthy_peers,
+            min_healthy_peers: details.min_healthy_peers,
+            peers: details.peers.into_iter().map(Into::into).collect(),
+        }
+    }
 }
 
 #[derive(Serialize, Debug)]
@@ -232,10 +845,178 @@ struct RemoveWordRequest {
 
 #[derive(Serialize, Debug)]
 struct GetWordResponse {
-    pub word: String,
+    #[serde(flatten)]
+    pub record: WordRecord,
+}
+
+#[derive(Deserialize, Debug)]
+struct ListWordsQuery {
+    pub prefix: Option<String>,
+    pub limit: Option<usize>,
+    pub cursor: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+struct ListWordsResponse {
+    pub words: Vec<String>,
+    pub next_cursor: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct SetWeightRequest {
+    pub weight: f64,
 }
 
 #[derive(Deserialize, Debug)]
 struct AddWordRequest {
     pub word: String,
+    #[serde(flatten)]
+    pub metadata: WordMetadata,
+}
+
+/// Splits an import body into lines, a line longer than the limit being reported as `None` rather
+/// than ending the body.
+struct ImportLineCodec(LinesCodec);
+
+impl Decoder for ImportLineCodec {
+    type Item = Option<String>;
+    type Error = LinesCodecError;
+
+    fn decode(&mut self, buf: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        match self.0.decode(buf) {
+            Err(LinesCodecError::MaxLineLengthExceeded) => Ok(Some(None)),
+            result => result.map(|line| line.map(Some)),
+        }
+    }
+
+    fn decode_eof(&mut self, buf: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        match self.0.decode_eof(buf) {
+            Err(LinesCodecError::MaxLineLengthExceeded) => Ok(Some(None)),
+            result => result.map(|line| line.map(Some)),
+        }
+    }
+}
+
+/// Line-oriented formats accepted by the import and export endpoints.
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+enum WordFormat {
+    Ndjson,
+    Csv,
+    Text,
+}
+
+impl WordFormat {
+    fn from_media_type(content_type: &str) -> Option<Self> {
+        let media_type = content_type.split(';').next().unwrap_or_default().trim();
+        match media_type {
+            "application/x-ndjson" | "application/jsonl" => Some(Self::Ndjson),
+            "text/csv" => Some(Self::Csv),
+            "text/plain" => Some(Self::Text),
+            _ => None,
+        }
+    }
+
+    fn media_type(&self) -> &'static str {
+        match self {
+            Self::Ndjson => "application/x-ndjson",
+            Self::Csv => "text/csv; header=present",
+            Self::Text => "text/plain",
+        }
+    }
+
+    /// Extracts the word of a line, `None` meaning the line should be skipped.
+    fn parse_line(&self, line: &str) -> Option<Result<(String, WordMetadata), String>> {
+        let line = line.trim();
+        if line.is_empty() {
+            return None;
+        }
+
+        match self {
+            Self::Text => Some(Ok((line.to_string(), WordMetadata::default()))),
+            Self::Ndjson => Some(match serde_json::from_str::<NdjsonWord>(line) {
+                Ok(NdjsonWord::Word(word)) => Ok((word, WordMetadata::default())),
+                Ok(NdjsonWord::Object { word, metadata }) => Ok((word, metadata)),
+                Err(e) => Err(format!("Invalid JSON line: {}", e)),
+            }),
+            Self::Csv => {
+                Some(parse_csv_first_field(line).map(|word| (word, WordMetadata::default())))
+            }
+        }
+    }
+
+    fn format_record(&self, record: &WordRecord) -> Vec<u8> {
+        let word = &record.word;
+        let mut line = match self {
+            Self::Ndjson => serde_json::to_vec(record).unwrap_or_default(),
+            Self::Csv if word.contains([',', '"', '\n', '\r']) => {
+                format!("\"{}\"", word.replace('"', "\"\"")).into_bytes()
+            }
+            Self::Csv | Self::Text => word.as_bytes().to_vec(),
+        };
+        line.push(b'\n');
+        line
+    }
+}
+
+/// Tells whether a CSV body starts with a header line, through the `header` parameter of its
+/// media type (RFC 4180), which exports set.
+fn has_header(content_type: &str) -> bool {
+    content_type.split(';').skip(1).any(|parameter| {
+        parameter.split_once('=').is_some_and(|(name, value)| {
+            name.trim().eq_ignore_ascii_case("header")
+                && value.trim().eq_ignore_ascii_case("present")
+        })
+    })
+}
+
+fn parse_csv_first_field(line: &str) -> Result<String, String> {
+    let Some(quoted) = line.strip_prefix('"') else {
+        return Ok(line
+            .split(',')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_string());
+    };
+
+    let mut word = String::new();
+    let mut chars = quoted.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if chars.peek() == Some(&'"') => {
+                chars.next();
+                word.push('"');
+            }
+            '"' => return Ok(word),
+            c => word.push(c),
+        }
+    }
+    Err("Unterminated quoted field".to_string())
+}
+
+/// A line of an NDJSON import, either a bare JSON string or an object with metadata.
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+enum NdjsonWord {
+    Word(String),
+    Object {
+        word: String,
+        #[serde(flatten)]
+        metadata: WordMetadata,
+    },
+}
+
+#[derive(Serialize, Debug)]
+#[serde(tag = "status", rename_all = "snake_case")]
+enum ImportResult {
+    Created { word: String },
+    AlreadyExists { word: String },
+    Invalid { line: usize, error: String },
+    Failed { error: String },
+}
+
+#[derive(Deserialize, Debug)]
+struct ExportQuery {
+    pub format: Option<WordFormat>,
 }
@@ -0,0 +1,3 @@
+//! Stores of the example service, exposed for the benchmarks.
+
+pub mod stores;
@@ -1,17 +1,32 @@
 mod clients;
 mod core;
+mod discovery;
 mod interfaces;
-mod stores;
+mod membership;
+mod strategies;
 
-use crate::clients::grpc::{GrpcClient, GrpcClientError};
+use example_service::stores;
+
+use crate::clients::grpc::GrpcClient;
 use crate::clients::Client;
-use crate::core::Core;
+use crate::core::{ChainLimits, Core, CoreConfig, FailurePolicy, ProbeConfig};
+use crate::discovery::dns::{DnsDiscovery, DnsRecord};
+use crate::discovery::file::FileDiscovery;
+use crate::discovery::static_list::StaticDiscovery;
+use crate::discovery::{run_discovery, Discovery, DiscoveryError};
 use crate::stores::Store;
+use crate::strategies::ChainStrategyKind;
 use config::{Config, ConfigError};
 use interfaces::{
-    grpc::{word::word_service_server::WordServiceServer, GrpcInterface, GrpcInterfaceError},
+    grpc::{
+        report_health,
+        word::membership_service_server::MembershipServiceServer,
+        word::{admin_service_server::AdminServiceServer, word_service_server::WordServiceServer},
+        GrpcInterface, GrpcInterfaceError, MembershipInterface, FILE_DESCRIPTOR_SET,
+    },
     http::{HttpInterface, HttpInterfaceError},
 };
+use membership::{Membership, MembershipConfig};
 use opentelemetry::global;
 use opentelemetry::logs::LoggerProvider;
 use opentelemetry::metrics::MeterProvider;
@@ -29,25 +44,51 @@ use opentelemetry_sdk::Resource;
 use serde::{Deserialize, Serialize};
 use std::future::Future;
 use std::str::FromStr;
+use std::time::Duration;
 use std::{net::AddrParseError, sync::Arc};
+use stores::file::{FileStore, FileStoreError};
 use stores::hashmap::{HashmapStore, HashmapStoreError};
+use stores::seed::{load_seed, seed_store, SeedError, SeedSource};
+use stores::sqlite::{SqliteStore, SqliteStoreError};
 use thiserror::Error;
 use tokio::sync::RwLock;
 use tonic::transport::Server;
+use tonic_health::pb::health_server::HealthServer;
+use tonic_health::server::{HealthReporter, HealthService};
 use tonic_tracing_opentelemetry::middleware::{filters, server};
-use tracing::{debug, error, info};
+use tracing::info;
 use tracing_opentelemetry::MetricsLayer;
 use tracing_subscriber::filter::Directive;
 use tracing_subscriber::layer::SubscriberExt;
 use tracing_subscriber::Registry;
 use tracing_subscriber::{prelude::*, EnvFilter};
 
+const SQLITE_DATABASE_FILE: &str = "words.db";
+
 #[derive(Error, Debug)]
 enum ExampleAppError {
     #[error("Config error")]
     ConfigError(#[source] ConfigError),
     #[error("Hashmap store error")]
     HashmapStoreError(#[source] HashmapStoreError),
+    #[error("File store error")]
+    FileStoreError(#[source] FileStoreError),
+    #[error("SQLite store error")]
+    SqliteStoreError(#[source] SqliteStoreError),
+    #[error("Error when seeding store")]
+    SeedError(#[source] SeedError),
+    #[error("Failed to create data directory {path:?}")]
+    DataDirError {
+        #[source]
+        source: std::io::Error,
+        path: String,
+    },
+    #[error("Failed to parse local IP {ip:?}")]
+    LocalIpParseError {
+        #[source]
+        source: AddrParseError,
+        ip: String,
+    },
     #[error("Failed to parse url for port {port:?}")]
     UrlParseError {
         #[source]
@@ -58,8 +99,12 @@ enum ExampleAppError {
     HttpServerError(#[source] HttpInterfaceError),
     #[error("gRPC server error")]
     GrpcServerError(#[source] GrpcInterfaceError),
-    #[error("gRPC client error")]
-    GrpcClientError(#[source] GrpcClientError),
+    #[error("Error when building gRPC reflection service")]
+    ReflectionError(#[source] tonic_reflection::server::Error),
+    #[error("Peer discovery error")]
+    DiscoveryError(#[source] DiscoveryError),
+    #[error("DISCOVERY_GOSSIP_ADVERTISE_URL is required with the gossip discovery backend")]
+    MissingAdvertiseUrl,
     #[error("Error when building OpenTelemetry span exporter")]
     SpanExporterBuildError(#[source] opentelemetry_otlp::ExporterBuildError),
     #[error("Error when building OpenTelemetry metrics exporter")]
@@ -75,6 +120,74 @@ struct ExampleAppConfig {
     http_port: u16,
     grpc_port: u16,
     connected_services: String,
+    service_id: String,
+    chain_strategy: ChainStrategyKind,
+    max_chain_count: u32,
+    max_input_length: usize,
+    max_word_length: usize,
+    failure_policy: FailurePolicy,
+    max_hop_retries: u32,
+    /// Serve gRPC reflection, letting tools such as grpcurl call the services without the proto file
+    grpc_reflection: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "lowercase")]
+enum StoreBackend {
+    Hashmap,
+    File,
+    Sqlite,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct StoreConfig {
+    backend: StoreBackend,
+    data_dir: String,
+    snapshot_interval: u64,
+    seed_source: SeedSource,
+    seed_path: String,
+    seed_only_if_empty: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "lowercase")]
+enum DiscoveryBackend {
+    /// Services listed in EXAMPLE_SERVICE_CONNECTED_SERVICES
+    Static,
+    Dns,
+    File,
+    /// Members of the gossip membership, joined through DISCOVERY_GOSSIP_SEEDS
+    Gossip,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct DiscoveryConfig {
+    backend: DiscoveryBackend,
+    refresh_interval: u64,
+    dns_name: String,
+    dns_record: DnsRecord,
+    dns_port: u16,
+    /// Address of this instance, left out of the peers found
+    dns_local_ip: String,
+    file_path: String,
+    gossip_seeds: String,
+    /// gRPC url other members reach this instance at, required with the gossip backend
+    gossip_advertise_url: String,
+    gossip_protocol_period_ms: u64,
+    gossip_ping_timeout_ms: u64,
+    gossip_indirect_probes: usize,
+    gossip_suspicion_timeout_ms: u64,
+    gossip_tombstone_timeout_ms: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct HealthConfig {
+    probe_interval_ms: u64,
+    probe_timeout_ms: u64,
+    failure_threshold: u32,
+    recovery_threshold: u32,
+    degraded_latency_ms: u64,
+    min_healthy_peers: usize,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -82,7 +195,16 @@ struct MonitoringConfig {
     metrics_push_interval: u64,
 }
 
-fn init_config() -> Result<(ExampleAppConfig, MonitoringConfig), ExampleAppError> {
+fn init_config() -> Result<
+    (
+        ExampleAppConfig,
+        StoreConfig,
+        DiscoveryConfig,
+        HealthConfig,
+        MonitoringConfig,
+    ),
+    ExampleAppError,
+> {
     let app_config = Config::builder()
         .add_source(config::Environment::default().prefix("EXAMPLE_SERVICE"))
         .set_default("http_port", 3001)
@@ -91,6 +213,98 @@ fn init_config() -> Result<(ExampleAppConfig, MonitoringConfig), ExampleAppError
         .map_err(ExampleAppError::ConfigError)?
         .set_default("connected_services", "")
         .map_err(ExampleAppError::ConfigError)?
+        .set_default(
+            "service_id",
+            std::env::var("HOSTNAME").unwrap_or("example-service".to_string()),
+        )
+        .map_err(ExampleAppError::ConfigError)?
+        .set_default("chain_strategy", "random")
+        .map_err(ExampleAppError::ConfigError)?
+        .set_default("max_chain_count", 100)
+        .map_err(ExampleAppError::ConfigError)?
+        .set_default("max_input_length", 100)
+        .map_err(ExampleAppError::ConfigError)?
+        .set_default("max_word_length", 64)
+        .map_err(ExampleAppError::ConfigError)?
+        .set_default("failure_policy", "fail_fast")
+        .map_err(ExampleAppError::ConfigError)?
+        .set_default("max_hop_retries", 2)
+        .map_err(ExampleAppError::ConfigError)?
+        .set_default("grpc_reflection", false)
+        .map_err(ExampleAppError::ConfigError)?
+        .build()
+        .map_err(ExampleAppError::ConfigError)?
+        .try_deserialize()
+        .map_err(ExampleAppError::ConfigError)?;
+
+    let store_config = Config::builder()
+        .add_source(config::Environment::default().prefix("STORE"))
+        .set_default("backend", "hashmap")
+        .map_err(ExampleAppError::ConfigError)?
+        .set_default("data_dir", "data")
+        .map_err(ExampleAppError::ConfigError)?
+        .set_default("snapshot_interval", 60)
+        .map_err(ExampleAppError::ConfigError)?
+        .set_default("seed_source", "default")
+        .map_err(ExampleAppError::ConfigError)?
+        .set_default("seed_path", "")
+        .map_err(ExampleAppError::ConfigError)?
+        .set_default("seed_only_if_empty", true)
+        .map_err(ExampleAppError::ConfigError)?
+        .build()
+        .map_err(ExampleAppError::ConfigError)?
+        .try_deserialize()
+        .map_err(ExampleAppError::ConfigError)?;
+
+    let discovery_config = Config::builder()
+        .add_source(config::Environment::default().prefix("DISCOVERY"))
+        .set_default("backend", "static")
+        .map_err(ExampleAppError::ConfigError)?
+        .set_default("refresh_interval", 30)
+        .map_err(ExampleAppError::ConfigError)?
+        .set_default("dns_name", "")
+        .map_err(ExampleAppError::ConfigError)?
+        .set_default("dns_record", "a")
+        .map_err(ExampleAppError::ConfigError)?
+        .set_default("dns_port", 50051)
+        .map_err(ExampleAppError::ConfigError)?
+        .set_default("dns_local_ip", "")
+        .map_err(ExampleAppError::ConfigError)?
+        .set_default("file_path", "peers.txt")
+        .map_err(ExampleAppError::ConfigError)?
+        .set_default("gossip_seeds", "")
+        .map_err(ExampleAppError::ConfigError)?
+        .set_default("gossip_advertise_url", "")
+        .map_err(ExampleAppError::ConfigError)?
+        .set_default("gossip_protocol_period_ms", 1000)
+        .map_err(ExampleAppError::ConfigError)?
+        .set_default("gossip_ping_timeout_ms", 300)
+        .map_err(ExampleAppError::ConfigError)?
+        .set_default("gossip_indirect_probes", 3)
+        .map_err(ExampleAppError::ConfigError)?
+        .set_default("gossip_suspicion_timeout_ms", 5000)
+        .map_err(ExampleAppError::ConfigError)?
+        .set_default("gossip_tombstone_timeout_ms", 60000)
+        .map_err(ExampleAppError::ConfigError)?
+        .build()
+        .map_err(ExampleAppError::ConfigError)?
+        .try_deserialize()
+        .map_err(ExampleAppError::ConfigError)?;
+
+    let health_config = Config::builder()
+        .add_source(config::Environment::default().prefix("HEALTH"))
+        .set_default("probe_interval_ms", 5000)
+        .map_err(ExampleAppError::ConfigError)?
+        .set_default("probe_timeout_ms", 1000)
+        .map_err(ExampleAppError::ConfigError)?
+        .set_default("failure_threshold", 3)
+        .map_err(ExampleAppError::ConfigError)?
+        .set_default("recovery_threshold", 2)
+        .map_err(ExampleAppError::ConfigError)?
+        .set_default("degraded_latency_ms", 500)
+        .map_err(ExampleAppError::ConfigError)?
+        .set_default("min_healthy_peers", 0)
+        .map_err(ExampleAppError::ConfigError)?
         .build()
         .map_err(ExampleAppError::ConfigError)?
         .try_deserialize()
@@ -105,7 +319,13 @@ fn init_config() -> Result<(ExampleAppConfig, MonitoringConfig), ExampleAppError
         .try_deserialize()
         .map_err(ExampleAppError::ConfigError)?;
 
-    Ok((app_config, monitoring_config))
+    Ok((
+        app_config,
+        store_config,
+        discovery_config,
+        health_config,
+        monitoring_config,
+    ))
 }
 
 pub struct OtelGuard {
@@ -241,16 +461,106 @@ fn init_tracing(config: &MonitoringConfig) -> Result<OtelGuard, ExampleAppError>
     })
 }
 
-async fn init_store() -> Result<impl Store, ExampleAppError> {
+async fn init_hashmap_store() -> Result<impl Store, ExampleAppError> {
     info!("Building hashmap store...");
     Ok(HashmapStore::new()
         .await
         .map_err(ExampleAppError::HashmapStoreError)?)
 }
 
+async fn init_file_store(
+    config: &StoreConfig,
+) -> Result<
+    (
+        impl Store,
+        impl Future<Output = Result<(), ExampleAppError>>,
+    ),
+    ExampleAppError,
+> {
+    info!("Building file store in {0}...", config.data_dir);
+    let store = FileStore::open(&config.data_dir)
+        .await
+        .map_err(ExampleAppError::FileStoreError)?;
+
+    let snapshot_task = {
+        let store_clone = store.clone();
+        let interval = Duration::from_secs(config.snapshot_interval);
+        async move {
+            store_clone.run_snapshots(interval).await;
+            Result::<(), ExampleAppError>::Ok(())
+        }
+    };
+
+    Ok((store, snapshot_task))
+}
+
+fn init_membership(
+    app_config: &ExampleAppConfig,
+    config: &DiscoveryConfig,
+) -> Result<Membership, ExampleAppError> {
+    info!("Building gossip membership...");
+    // No default would be reachable by other members, the local address in particular
+    if config.gossip_advertise_url.is_empty() {
+        return Err(ExampleAppError::MissingAdvertiseUrl);
+    }
+    Ok(Membership::new(MembershipConfig {
+        id: app_config.service_id.clone(),
+        advertise_url: config.gossip_advertise_url.clone(),
+        seeds: StaticDiscovery::new(&config.gossip_seeds).urls(),
+        protocol_period: Duration::from_millis(config.gossip_protocol_period_ms),
+        ping_timeout: Duration::from_millis(config.gossip_ping_timeout_ms),
+        indirect_probes: config.gossip_indirect_probes,
+        suspicion_timeout: Duration::from_millis(config.gossip_suspicion_timeout_ms),
+        tombstone_timeout: Duration::from_millis(config.gossip_tombstone_timeout_ms),
+    }))
+}
+
+/// Builds the peer discovery, along with the membership it relies on for the gossip backend.
+fn init_discovery(
+    app_config: &ExampleAppConfig,
+    config: &DiscoveryConfig,
+) -> Result<(Box<dyn Discovery>, Option<Membership>), ExampleAppError> {
+    info!("Building {:?} peer discovery...", config.backend);
+    Ok(match config.backend {
+        DiscoveryBackend::Static => (
+            Box::new(StaticDiscovery::new(&app_config.connected_services)),
+            None,
+        ),
+        DiscoveryBackend::Dns => {
+            let local_ip = match config.dns_local_ip.as_str() {
+                "" => None,
+                ip => Some(ip.parse().map_err(|e| ExampleAppError::LocalIpParseError {
+                    source: e,
+                    ip: ip.to_string(),
+                })?),
+            };
+            (
+                Box::new(
+                    DnsDiscovery::new(
+                        config.dns_name.clone(),
+                        config.dns_record,
+                        config.dns_port,
+                        local_ip,
+                    )
+                    .map_err(ExampleAppError::DiscoveryError)?,
+                ),
+                None,
+            )
+        }
+        DiscoveryBackend::File => (Box::new(FileDiscovery::new(&config.file_path)), None),
+        DiscoveryBackend::Gossip => {
+            let membership = init_membership(app_config, config)?;
+            (Box::new(membership.clone()), Some(membership))
+        }
+    })
+}
+
 fn init_core(
     store: impl Store,
     config: &ExampleAppConfig,
+    discovery: Box<dyn Discovery>,
+    discovery_config: &DiscoveryConfig,
+    health_config: &HealthConfig,
 ) -> Result<
     (
         Core<impl Store, impl Client>,
@@ -258,26 +568,45 @@ fn init_core(
     ),
     ExampleAppError,
 > {
-    info!("Building gRPC clients...");
-    let grpc_clients = Arc::new(RwLock::new(Vec::new()));
-    let grpc_clients_task = {
-        let grpc_clients_clone = grpc_clients.clone();
-        let urls = config.connected_services.clone();
+    let core_config = CoreConfig {
+        service_id: config.service_id.clone(),
+        chain_strategy: config.chain_strategy,
+        limits: ChainLimits {
+            max_chain_count: config.max_chain_count,
+            max_input_length: config.max_input_length,
+            max_word_length: config.max_word_length,
+        },
+        failure_policy: config.failure_policy,
+        max_hop_retries: config.max_hop_retries,
+        probe: ProbeConfig {
+            interval: Duration::from_millis(health_config.probe_interval_ms),
+            timeout: Duration::from_millis(health_config.probe_timeout_ms),
+            failure_threshold: health_config.failure_threshold,
+            recovery_threshold: health_config.recovery_threshold,
+            degraded_latency: Duration::from_millis(health_config.degraded_latency_ms),
+        },
+        min_healthy_peers: health_config.min_healthy_peers,
+    };
+
+    let core: Core<_, GrpcClient> =
+        Core::new(store, Arc::new(RwLock::new(Vec::new())), core_config);
+
+    let interval = match discovery_config.backend {
+        // Members are synced as often as they are probed
+        DiscoveryBackend::Gossip => {
+            Duration::from_millis(discovery_config.gossip_protocol_period_ms)
+        }
+        _ => Duration::from_secs(discovery_config.refresh_interval),
+    };
+    let discovery_task = {
+        let core_clone = core.clone();
         async move {
-            if !urls.is_empty() {
-                for service_url in urls.split(',') {
-                    let client = GrpcClient::new(service_url.to_string())
-                        .await
-                        .map_err(ExampleAppError::GrpcClientError)?;
-                    grpc_clients_clone.write().await.push(client);
-                    debug!("Connected gRPC client to {:?}", service_url);
-                }
-            }
+            run_discovery(discovery, core_clone, interval).await;
             Result::<(), ExampleAppError>::Ok(())
         }
     };
 
-    Ok((Core::new(store, grpc_clients), grpc_clients_task))
+    Ok((core, discovery_task))
 }
 
 fn init_http_interface(
@@ -295,10 +624,44 @@ fn init_http_interface(
     }
 }
 
+/// Leaves out of traces the health checks and the membership protocol, which run continuously.
+fn reject_probes(path: &str) -> bool {
+    filters::reject_healthcheck(path) && !path.contains("word.MembershipService")
+}
+
+/// Describes the word services along with the health service, the reflection service describing
+/// itself.
+fn reflection_builder() -> tonic_reflection::server::Builder<'static> {
+    tonic_reflection::server::Builder::configure()
+        .register_encoded_file_descriptor_set(FILE_DESCRIPTOR_SET)
+        .register_encoded_file_descriptor_set(tonic_health::pb::FILE_DESCRIPTOR_SET)
+}
+
 fn init_grpc_interface(
     core: Core<impl Store, impl Client>,
+    membership: Option<Membership>,
+    health_reporter: HealthReporter,
     config: &ExampleAppConfig,
 ) -> Result<impl Future<Output = Result<(), ExampleAppError>>, ExampleAppError> {
+    let membership_interface = membership
+        .map(|membership| MembershipServiceServer::new(MembershipInterface::new(membership)));
+    let health_interface = HealthServer::new(HealthService::from_health_reporter(health_reporter));
+    let (reflection_v1, reflection_v1alpha) = match config.grpc_reflection {
+        true => (
+            Some(
+                reflection_builder()
+                    .build_v1()
+                    .map_err(ExampleAppError::ReflectionError)?,
+            ),
+            Some(
+                reflection_builder()
+                    .build_v1alpha()
+                    .map_err(ExampleAppError::ReflectionError)?,
+            ),
+        ),
+        false => (None, None),
+    };
+    let admin_interface = GrpcInterface::new(core.clone());
     let grpc_interface = GrpcInterface::new(core);
     let grpc_url = format!("0.0.0.0:{0}", config.grpc_port)
         .parse()
@@ -309,8 +672,13 @@ fn init_grpc_interface(
     Ok(async move {
         info!("Starting gRPC interface on address {0}...", grpc_url);
         Server::builder()
-            .layer(server::OtelGrpcLayer::default().filter(filters::reject_healthcheck))
+            .layer(server::OtelGrpcLayer::default().filter(reject_probes))
             .add_service(WordServiceServer::new(grpc_interface))
+            .add_service(AdminServiceServer::new(admin_interface))
+            .add_service(health_interface)
+            .add_optional_service(membership_interface)
+            .add_optional_service(reflection_v1)
+            .add_optional_service(reflection_v1alpha)
             .serve(grpc_url)
             .await
             .map_err(|e| GrpcInterfaceError::GrpcServerError {
@@ -322,23 +690,146 @@ fn init_grpc_interface(
     })
 }
 
-#[tokio::main]
-async fn main() -> Result<(), ExampleAppError> {
-    let (app_config, monitoring_config) = init_config()?;
+async fn init_sqlite_store(config: &StoreConfig) -> Result<impl Store, ExampleAppError> {
+    info!("Building SQLite store in {0}...", config.data_dir);
+    tokio::fs::create_dir_all(&config.data_dir)
+        .await
+        .map_err(|e| ExampleAppError::DataDirError {
+            source: e,
+            path: config.data_dir.clone(),
+        })?;
 
-    let _guard = init_tracing(&monitoring_config)?;
+    SqliteStore::open(std::path::Path::new(&config.data_dir).join(SQLITE_DATABASE_FILE))
+        .await
+        .map_err(ExampleAppError::SqliteStoreError)
+}
 
-    info!("Starting example service...");
+async fn init_seed(
+    store: &mut impl Store,
+    config: &StoreConfig,
+    service_id: &str,
+) -> Result<(), ExampleAppError> {
+    info!("Seeding store from {:?} source...", config.seed_source);
+    let words = load_seed(config.seed_source, &config.seed_path)
+        .await
+        .map_err(ExampleAppError::SeedError)?;
+
+    seed_store(store, words, config.seed_only_if_empty, service_id)
+        .await
+        .map_err(ExampleAppError::SeedError)?;
+
+    Ok(())
+}
+
+async fn run(
+    mut store: impl Store,
+    store_task: impl Future<Output = Result<(), ExampleAppError>>,
+    app_config: &ExampleAppConfig,
+    store_config: &StoreConfig,
+    discovery_config: &DiscoveryConfig,
+    health_config: &HealthConfig,
+) -> Result<(), ExampleAppError> {
+    init_seed(&mut store, store_config, &app_config.service_id).await?;
+
+    let (discovery, membership) = init_discovery(app_config, discovery_config)?;
+    let membership_task = {
+        let membership_clone = membership.clone();
+        async move {
+            if let Some(membership) = membership_clone {
+                membership.run().await;
+            }
+            Result::<(), ExampleAppError>::Ok(())
+        }
+    };
 
-    let store = init_store().await?;
+    let (core, discovery_task) = init_core(
+        store,
+        app_config,
+        discovery,
+        discovery_config,
+        health_config,
+    )?;
+    let prober_task = {
+        let core_clone = core.clone();
+        async move {
+            core_clone.run_prober().await;
+            Result::<(), ExampleAppError>::Ok(())
+        }
+    };
 
-    let (core, client_task) = init_core(store, &app_config)?;
+    let http_server_task = init_http_interface(core.clone(), app_config);
 
-    let http_server_task = init_http_interface(core.clone(), &app_config);
+    let health_reporter = HealthReporter::new();
+    let health_task = {
+        let core_clone = core.clone();
+        let health_reporter = health_reporter.clone();
+        let interval = Duration::from_millis(health_config.probe_interval_ms);
+        async move {
+            report_health(core_clone, health_reporter, interval).await;
+            Result::<(), ExampleAppError>::Ok(())
+        }
+    };
 
-    let grpc_server_task = init_grpc_interface(core, &app_config)?;
+    let grpc_server_task = init_grpc_interface(core, membership, health_reporter, app_config)?;
 
-    tokio::try_join!(http_server_task, grpc_server_task, client_task)?;
+    tokio::try_join!(
+        http_server_task,
+        grpc_server_task,
+        discovery_task,
+        prober_task,
+        health_task,
+        membership_task,
+        store_task
+    )?;
 
     Ok(())
 }
+
+#[tokio::main]
+async fn main() -> Result<(), ExampleAppError> {
+    let (app_config, store_config, discovery_config, health_config, monitoring_config) =
+        init_config()?;
+
+    let _guard = init_tracing(&monitoring_config)?;
+
+    info!("Starting example service...");
+
+    match store_config.backend {
+        StoreBackend::Hashmap => {
+            let store = init_hashmap_store().await?;
+            run(
+                store,
+                async { Ok(()) },
+                &app_config,
+                &store_config,
+                &discovery_config,
+                &health_config,
+            )
+            .await
+        }
+        StoreBackend::File => {
+            let (store, store_task) = init_file_store(&store_config).await?;
+            run(
+                store,
+                store_task,
+                &app_config,
+                &store_config,
+                &discovery_config,
+                &health_config,
+            )
+            .await
+        }
+        StoreBackend::Sqlite => {
+            let store = init_sqlite_store(&store_config).await?;
+            run(
+                store,
+                async { Ok(()) },
+                &app_config,
+                &store_config,
+                &discovery_config,
+                &health_config,
+            )
+            .await
+        }
+    }
+}
@@ -0,0 +1,513 @@
+use crate::clients::grpc::{GrpcClientError, GrpcMembershipClient};
+use crate::clients::ClientError;
+use crate::discovery::{Discovery, DiscoveryError};
+use futures::future::join_all;
+use rand::seq::IndexedRandom;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{Mutex, RwLock};
+use tokio::time::MissedTickBehavior;
+use tonic::async_trait;
+use tracing::{debug, info, warn};
+
+/// State of a member, later states taking precedence over earlier ones at the same incarnation.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum MemberState {
+    Alive,
+    /// Could not be probed, declared dead unless it refutes it in time
+    Suspect,
+    Dead,
+}
+
+/// An example-service instance taking part in the membership.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Member {
+    pub id: String,
+    /// gRPC url the member is reached at
+    pub url: String,
+    /// Raised by the member itself to refute a suspicion
+    pub incarnation: u64,
+    pub state: MemberState,
+}
+
+impl Member {
+    /// Tells whether `self` is newer news about a member than `other`.
+    fn supersedes(&self, other: &Member) -> bool {
+        self.incarnation > other.incarnation
+            || (self.incarnation == other.incarnation && self.state > other.state)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MembershipConfig {
+    /// Identifier the local instance is known by, its service id
+    pub id: String,
+    /// gRPC url other members reach the local instance at
+    pub advertise_url: String,
+    /// Urls of the instances asked to join the membership, any single one being enough
+    pub seeds: Vec<String>,
+    /// Interval at which a member is probed
+    pub protocol_period: Duration,
+    /// Time a probed member has to answer
+    pub ping_timeout: Duration,
+    /// Number of members asked to probe a member that did not answer
+    pub indirect_probes: usize,
+    /// Time a suspected member has to refute the suspicion before being declared dead
+    pub suspicion_timeout: Duration,
+    /// Time a dead member is remembered for, so that stale views still listing it as alive do
+    /// not bring it back
+    pub tombstone_timeout: Duration,
+}
+
+#[derive(Debug)]
+struct MemberEntry {
+    member: Member,
+    changed_at: Instant,
+}
+
+#[derive(Debug, Default)]
+struct MembershipState {
+    incarnation: u64,
+    /// Other members, by id
+    members: HashMap<String, MemberEntry>,
+}
+
+/// SWIM-style membership: each protocol period a random member is probed, directly then through
+/// other members, those that fail being suspected then declared dead. Members exchange their
+/// whole view with each probe, which suits the handful of instances of a deployment.
+#[derive(Clone, Debug)]
+pub struct Membership {
+    config: Arc<MembershipConfig>,
+    state: Arc<RwLock<MembershipState>>,
+    clients: Arc<Mutex<HashMap<String, GrpcMembershipClient>>>,
+}
+
+impl Membership {
+    pub fn new(config: MembershipConfig) -> Self {
+        Membership {
+            config: Arc::new(config),
+            state: Arc::new(RwLock::new(MembershipState::default())),
+            clients: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Members known to the local instance, itself included.
+    pub async fn members(&self) -> Vec<Member> {
+        let state = self.state.read().await;
+        let local = Member {
+            id: self.config.id.clone(),
+            url: self.config.advertise_url.clone(),
+            incarnation: state.incarnation,
+            state: MemberState::Alive,
+        };
+        std::iter::once(local)
+            .chain(state.members.values().map(|entry| entry.member.clone()))
+            .collect()
+    }
+
+    /// Merges the view of another member into the local one.
+    pub async fn merge(&self, members: Vec<Member>) {
+        let mut state = self.state.write().await;
+        for member in members {
+            if member.id == self.config.id {
+                if member.state != MemberState::Alive && member.incarnation >= state.incarnation {
+                    state.incarnation = member.incarnation + 1;
+                    info!(
+                        "Refuting {0:?} state with incarnation {1}",
+                        member.state, state.incarnation
+                    );
+                }
+                continue;
+            }
+
+            match state.members.get_mut(&member.id) {
+                Some(entry) if member.supersedes(&entry.member) => {
+                    if member.state != entry.member.state {
+                        info!(
+                            "Member {0} is now {1:?}, was {2:?}",
+                            member.id, member.state, entry.member.state
+                        );
+                    }
+                    entry.member = member;
+                    entry.changed_at = Instant::now();
+                }
+                Some(_) => {}
+                // Dead members are not learnt about, they would only be forgotten later on, while
+                // those already forgotten are not remembered by stale views for long
+                None if member.state != MemberState::Dead => {
+                    info!("Member {0} joined at {1}", member.id, member.url);
+                    state.members.insert(
+                        member.id.clone(),
+                        MemberEntry {
+                            member,
+                            changed_at: Instant::now(),
+                        },
+                    );
+                }
+                None => {}
+            }
+        }
+    }
+
+    /// Runs the protocol, joining through the seeds as long as no other member is known.
+    pub async fn run(self) {
+        let mut ticker = tokio::time::interval(self.config.protocol_period);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            ticker.tick().await;
+            self.expire().await;
+            match self.pick_target().await {
+                Some(target) => self.probe(target).await,
+                None => self.join().await,
+            }
+        }
+    }
+
+    /// Probes `url` on behalf of another member, merging its view first.
+    pub async fn indirect_ping(&self, url: &str, members: Vec<Member>) -> bool {
+        self.merge(members).await;
+        self.gossip(url).await.is_ok()
+    }
+
+    async fn pick_target(&self) -> Option<Member> {
+        let state = self.state.read().await;
+        let candidates: Vec<&Member> = state
+            .members
+            .values()
+            .map(|entry| &entry.member)
+            .filter(|member| member.state != MemberState::Dead)
+            .collect();
+        candidates
+            .choose(&mut rand::rng())
+            .map(|member| (*member).clone())
+    }
+
+    #[tracing::instrument(fields(component = "Membership"), skip(self))]
+    async fn join(&self) {
+        for seed in self
+            .config
+            .seeds
+            .iter()
+            .filter(|seed| **seed != self.config.advertise_url)
+        {
+            match self.gossip(seed).await {
+                Ok(()) => {
+                    info!("Joined the membership through seed {0}", seed);
+                    return;
+                }
+                Err(e) => debug!("Failed to join through seed {0}: {1:?}", seed, e),
+            }
+        }
+    }
+
+    #[tracing::instrument(fields(component = "Membership"), skip(self))]
+    async fn probe(&self, target: Member) {
+        if self.gossip(&target.url).await.is_ok() {
+            return;
+        }
+
+        let helpers: Vec<Member> = {
+            let state = self.state.read().await;
+            let candidates: Vec<&Member> = state
+                .members
+                .values()
+                .map(|entry| &entry.member)
+                .filter(|member| member.state == MemberState::Alive && member.id != target.id)
+                .collect();
+            candidates
+                .choose_multiple(&mut rand::rng(), self.config.indirect_probes)
+                .map(|member| (*member).clone())
+                .collect()
+        };
+
+        let members = self.members().await;
+        let acks = join_all(helpers.iter().map(|helper| {
+            let members = members.clone();
+            let target_url = target.url.clone();
+            async move {
+                let mut client = self.client(&helper.url).await?;
+                // The helper has a whole ping timeout to probe the target
+                let response = tokio::time::timeout(
+                    self.config.ping_timeout * 2,
+                    client.indirect_ping(target_url, members),
+                )
+                .await
+                .map_err(|_| ClientError::DeadlineExceeded)??;
+                self.merge(response.1).await;
+                Ok::<bool, ClientError<GrpcClientError>>(response.0)
+            }
+        }))
+        .await;
+
+        if !acks.into_iter().any(|ack| ack.is_ok_and(|acked| acked)) {
+            self.suspect(&target).await;
+        }
+    }
+
+    /// Sends the local view to the member at `url`, merging the one it answers with.
+    async fn gossip(&self, url: &str) -> Result<(), ClientError<GrpcClientError>> {
+        let mut client = self.client(url).await?;
+        let members = tokio::time::timeout(
+            self.config.ping_timeout,
+            client.gossip(self.members().await),
+        )
+        .await
+        .map_err(|_| ClientError::DeadlineExceeded)??;
+        self.merge(members).await;
+        Ok(())
+    }
+
+    async fn client(
+        &self,
+        url: &str,
+    ) -> Result<GrpcMembershipClient, ClientError<GrpcClientError>> {
+        let mut clients = self.clients.lock().await;
+        if let Some(client) = clients.get(url) {
+            return Ok(client.clone());
+        }
+        let client = GrpcMembershipClient::new(url.to_string())
+            .map_err(ClientError::_InternalClientError)?;
+        clients.insert(url.to_string(), client.clone());
+        Ok(client)
+    }
+
+    async fn suspect(&self, target: &Member) {
+        let mut state = self.state.write().await;
+        if let Some(entry) = state.members.get_mut(&target.id) {
+            // A newer incarnation was learnt about in the meantime
+            if entry.member.incarnation == target.incarnation
+                && entry.member.state == MemberState::Alive
+            {
+                warn!("Member {0} did not answer probes, suspecting it", target.id);
+                entry.member.state = MemberState::Suspect;
+                entry.changed_at = Instant::now();
+            }
+        }
+    }
+
+    /// Declares dead the members suspected for too long, and forgets those dead for long enough
+    /// that no view lists them as alive anymore.
+    async fn expire(&self) {
+        let mut forgotten = Vec::new();
+        {
+            let mut state = self.state.write().await;
+            for entry in state.members.values_mut() {
+                match entry.member.state {
+                    MemberState::Suspect
+                        if entry.changed_at.elapsed() >= self.config.suspicion_timeout =>
+                    {
+                        warn!("Member {0} is dead", entry.member.id);
+                        entry.member.state = MemberState::Dead;
+                        entry.changed_at = Instant::now();
+                    }
+                    MemberState::Dead
+                        if entry.changed_at.elapsed() >= self.config.tombstone_timeout =>
+                    {
+                        forgotten.push(entry.member.clone())
+                    }
+                    _ => {}
+                }
+            }
+            for member in forgotten.iter() {
+                state.members.remove(&member.id);
+            }
+        }
+
+        let mut clients = self.clients.lock().await;
+        for member in forgotten {
+            debug!("Forgetting dead member {0}", member.id);
+            clients.remove(&member.url);
+        }
+    }
+}
+
+/// Peers are the members that are not dead, suspected members being kept until they are.
+#[async_trait]
+impl Discovery for Membership {
+    async fn discover(&mut self) -> Result<Vec<String>, DiscoveryError> {
+        Ok(self
+            .members()
+            .await
+            .into_iter()
+            .filter(|member| member.id != self.config.id && member.state != MemberState::Dead)
+            .map(|member| member.url)
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interfaces::grpc::word::membership_service_server::MembershipServiceServer;
+    use crate::interfaces::grpc::MembershipInterface;
+    use std::future::Future;
+    use tokio::sync::oneshot;
+    use tokio::task::JoinHandle;
+    use tonic::transport::server::TcpIncoming;
+    use tonic::transport::Server;
+
+    fn config(id: &str, advertise_url: String, seeds: Vec<String>) -> MembershipConfig {
+        MembershipConfig {
+            id: id.to_string(),
+            advertise_url,
+            seeds,
+            protocol_period: Duration::from_millis(100),
+            ping_timeout: Duration::from_millis(100),
+            indirect_probes: 1,
+            suspicion_timeout: Duration::from_millis(500),
+            tombstone_timeout: Duration::from_secs(60),
+        }
+    }
+
+    fn member(id: &str, incarnation: u64, state: MemberState) -> Member {
+        Member {
+            id: id.to_string(),
+            url: format!("http://{0}:50051", id),
+            incarnation,
+            state,
+        }
+    }
+
+    async fn state_of(membership: &Membership, id: &str) -> Option<MemberState> {
+        membership
+            .members()
+            .await
+            .into_iter()
+            .find(|member| member.id == id)
+            .map(|member| member.state)
+    }
+
+    /// Polls `condition` until it holds, panicking after a few seconds.
+    async fn eventually<F, Fut>(what: &str, condition: F)
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = bool>,
+    {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while !condition().await {
+            assert!(
+                Instant::now() < deadline,
+                "timed out waiting until {0}",
+                what
+            );
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    }
+
+    struct Instance {
+        membership: Membership,
+        shutdown: oneshot::Sender<()>,
+        protocol: JoinHandle<()>,
+    }
+
+    /// Serves the membership protocol on a free localhost port, joining through `seed` if any.
+    fn start(id: &str, seed: Option<&str>) -> (Instance, String) {
+        let incoming = TcpIncoming::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+        let url = format!("http://{0}", incoming.local_addr().unwrap());
+        let seeds = seed.into_iter().map(str::to_string).collect();
+        let membership = Membership::new(config(id, url.clone(), seeds));
+        let (shutdown, stopped) = oneshot::channel();
+        tokio::spawn(
+            Server::builder()
+                .add_service(MembershipServiceServer::new(MembershipInterface::new(
+                    membership.clone(),
+                )))
+                .serve_with_incoming_shutdown(incoming, async {
+                    let _ = stopped.await;
+                }),
+        );
+        let protocol = tokio::spawn(membership.clone().run());
+        let instance = Instance {
+            membership,
+            shutdown,
+            protocol,
+        };
+        (instance, url)
+    }
+
+    #[tokio::test]
+    async fn members_converge_then_detect_stopped_ones() {
+        let (first, seed) = start("a", None);
+        let (second, _) = start("b", Some(&seed));
+        let (third, _) = start("c", Some(&seed));
+
+        for instance in [&first, &second, &third] {
+            eventually("every member knows the others", || async {
+                let members = instance.membership.members().await;
+                members.len() == 3
+                    && members
+                        .iter()
+                        .all(|member| member.state == MemberState::Alive)
+            })
+            .await;
+        }
+
+        third.protocol.abort();
+        let _ = third.shutdown.send(());
+        for instance in [&first, &second] {
+            eventually("the stopped member is suspected", || async {
+                state_of(&instance.membership, "c").await != Some(MemberState::Alive)
+            })
+            .await;
+            eventually("the stopped member is dead", || async {
+                state_of(&instance.membership, "c").await == Some(MemberState::Dead)
+            })
+            .await;
+        }
+        let mut peers = first.membership.clone().discover().await.unwrap();
+        peers.sort();
+        assert_eq!(peers, vec![second.membership.config.advertise_url.clone()]);
+    }
+
+    #[tokio::test]
+    async fn suspected_members_are_declared_dead_after_the_timeout() {
+        let membership = Membership::new(config("a", String::new(), Vec::new()));
+        membership
+            .merge(vec![member("b", 0, MemberState::Alive)])
+            .await;
+        let target = member("b", 0, MemberState::Alive);
+        membership.suspect(&target).await;
+        assert_eq!(state_of(&membership, "b").await, Some(MemberState::Suspect));
+
+        membership.expire().await;
+        assert_eq!(state_of(&membership, "b").await, Some(MemberState::Suspect));
+        tokio::time::sleep(membership.config.suspicion_timeout).await;
+        membership.expire().await;
+        assert_eq!(state_of(&membership, "b").await, Some(MemberState::Dead));
+    }
+
+    #[tokio::test]
+    async fn stale_views_do_not_bring_dead_members_back() {
+        let membership = Membership::new(config("a", String::new(), Vec::new()));
+        membership
+            .merge(vec![member("b", 3, MemberState::Dead)])
+            .await;
+        assert_eq!(state_of(&membership, "b").await, None);
+
+        membership
+            .merge(vec![member("b", 3, MemberState::Alive)])
+            .await;
+        membership
+            .merge(vec![member("b", 3, MemberState::Dead)])
+            .await;
+        // Past the suspicion timeout the member is still remembered as dead
+        tokio::time::sleep(membership.config.suspicion_timeout).await;
+        membership.expire().await;
+        membership
+            .merge(vec![
+                member("b", 2, MemberState::Alive),
+                member("b", 3, MemberState::Suspect),
+            ])
+            .await;
+        assert_eq!(state_of(&membership, "b").await, Some(MemberState::Dead));
+
+        // Refuted by the member itself
+        membership
+            .merge(vec![member("b", 4, MemberState::Alive)])
+            .await;
+        assert_eq!(state_of(&membership, "b").await, Some(MemberState::Alive));
+    }
+}
@@ -0,0 +1,661 @@
+use crate::stores::hashmap::{HashmapStore, HashmapStoreError};
+use crate::stores::markov::TransitionCount;
+use crate::stores::{AddWordStatus, Store, StoreError, WordMetadata, WordPage, WordRecord};
+use futures::stream::{BoxStream, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+use tokio::fs::{self, File, OpenOptions};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+use tonic::async_trait;
+use tracing::{debug, error, trace, warn};
+
+const SNAPSHOT_FILE: &str = "words.snapshot";
+const SNAPSHOT_TMP_FILE: &str = "words.snapshot.tmp";
+const WAL_FILE: &str = "words.wal";
+
+#[derive(Error, Debug)]
+pub enum FileStoreError {
+    #[error("IO error on file {path:?}")]
+    Io {
+        #[source]
+        source: std::io::Error,
+        path: PathBuf,
+    },
+    #[error("Failed to serialize store entry")]
+    Serialization(#[source] serde_json::Error),
+    #[error("Corrupted entry at line {line} of file {path:?}")]
+    Corrupted {
+        #[source]
+        source: serde_json::Error,
+        path: PathBuf,
+        line: usize,
+    },
+    #[error("In-memory store error")]
+    Memory(#[source] HashmapStoreError),
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "op", content = "word", rename_all = "lowercase")]
+enum WalEntry {
+    Add(StoredRecord),
+    Remove(String),
+    /// Replaces the whole record of an existing word
+    Update(StoredRecord),
+    /// Counts the transitions of a sentence
+    Learn(Vec<String>),
+}
+
+/// Content of the snapshot file, which used to only hold the words.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(untagged)]
+enum StoredSnapshot {
+    Snapshot {
+        words: Vec<StoredRecord>,
+        #[serde(default)]
+        transitions: Vec<TransitionCount>,
+    },
+    Words(Vec<StoredRecord>),
+}
+
+/// Persisted form of a word, accepting the bare strings written before words carried metadata.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(untagged)]
+enum StoredRecord {
+    Record(WordRecord),
+    Word(String),
+}
+
+impl From<StoredRecord> for WordRecord {
+    fn from(stored: StoredRecord) -> Self {
+        match stored {
+            StoredRecord::Record(record) => record,
+            StoredRecord::Word(word) => WordRecord {
+                created_at: 0,
+                ..WordRecord::new(word, WordMetadata::default(), "")
+            },
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Wal {
+    file: File,
+    pending_entries: usize,
+}
+
+/// Store keeping words in memory, durably backed by a write-ahead log that is
+/// periodically compacted into a snapshot.
+#[derive(Clone, Debug)]
+pub struct FileStore {
+    memory: HashmapStore,
+    wal: Arc<Mutex<Wal>>,
+    data_dir: PathBuf,
+}
+
+impl FileStore {
+    pub async fn open(data_dir: impl AsRef<Path>) -> Result<FileStore, FileStoreError> {
+        let data_dir = data_dir.as_ref().to_path_buf();
+        fs::create_dir_all(&data_dir)
+            .await
+            .map_err(|e| FileStoreError::Io {
+                source: e,
+                path: data_dir.clone(),
+            })?;
+
+        let snapshot_path = data_dir.join(SNAPSHOT_FILE);
+        let wal_path = data_dir.join(WAL_FILE);
+        let (records, transitions) = load_snapshot(&snapshot_path).await?;
+        debug!(
+            "Loaded {0} words and {1} transitions from snapshot",
+            records.len(),
+            transitions.len()
+        );
+        let memory = HashmapStore::from_records(records);
+        {
+            let mut words = memory.word_store.write().await;
+            for transition in transitions {
+                words
+                    .transitions
+                    .add(transition.context, transition.next, transition.count);
+            }
+        }
+        replay_wal(&memory, &wal_path).await?;
+
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&wal_path)
+            .await
+            .map_err(|e| FileStoreError::Io {
+                source: e,
+                path: wal_path.clone(),
+            })?;
+
+        let store = FileStore {
+            memory,
+            wal: Arc::new(Mutex::new(Wal {
+                file,
+                pending_entries: 0,
+            })),
+            data_dir,
+        };
+
+        // Compact whatever was replayed so that the WAL starts empty
+        store.force_snapshot().await?;
+
+        Ok(store)
+    }
+
+    /// Writes a snapshot every `interval` if the WAL received entries since the last one.
+    pub async fn run_snapshots(self, interval: Duration) {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            if let Err(e) = self.snapshot().await {
+                error!("Failed to snapshot file store: {:?}", e);
+            }
+        }
+    }
+
+    #[tracing::instrument(fields(component = "File Store"), skip(self))]
+    pub async fn snapshot(&self) -> Result<(), FileStoreError> {
+        if self.wal.lock().await.pending_entries == 0 {
+            trace!("No pending WAL entries, skipping snapshot");
+            return Ok(());
+        }
+        self.force_snapshot().await
+    }
+
+    async fn force_snapshot(&self) -> Result<(), FileStoreError> {
+        let mut wal = self.wal.lock().await;
+
+        let records = self.memory.records().await;
+        let word_count = records.len();
+        let snapshot = StoredSnapshot::Snapshot {
+            words: records.into_iter().map(StoredRecord::Record).collect(),
+            transitions: self.memory.transition_counts().await,
+        };
+        let content = serde_json::to_vec(&snapshot).map_err(FileStoreError::Serialization)?;
+
+        let tmp_path = self.data_dir.join(SNAPSHOT_TMP_FILE);
+        let snapshot_path = self.data_dir.join(SNAPSHOT_FILE);
+        let mut tmp_file = File::create(&tmp_path)
+            .await
+            .map_err(|e| FileStoreError::Io {
+                source: e,
+                path: tmp_path.clone(),
+            })?;
+        tmp_file
+            .write_all(&content)
+            .await
+            .map_err(|e| FileStoreError::Io {
+                source: e,
+                path: tmp_path.clone(),
+            })?;
+        tmp_file.sync_all().await.map_err(|e| FileStoreError::Io {
+            source: e,
+            path: tmp_path.clone(),
+        })?;
+        fs::rename(&tmp_path, &snapshot_path)
+            .await
+            .map_err(|e| FileStoreError::Io {
+                source: e,
+                path: snapshot_path.clone(),
+            })?;
+
+        let wal_path = self.data_dir.join(WAL_FILE);
+        wal.file.set_len(0).await.map_err(|e| FileStoreError::Io {
+            source: e,
+            path: wal_path,
+        })?;
+        wal.pending_entries = 0;
+
+        debug!("Wrote snapshot of {0} words", word_count);
+
+        Ok(())
+    }
+
+    async fn append(
+        wal: &mut Wal,
+        path: PathBuf,
+        entries: &[WalEntry],
+    ) -> Result<(), FileStoreError> {
+        let mut lines = Vec::new();
+        for entry in entries {
+            serde_json::to_writer(&mut lines, entry).map_err(FileStoreError::Serialization)?;
+            lines.push(b'\n');
+        }
+
+        let len = wal
+            .file
+            .metadata()
+            .await
+            .map_err(|e| FileStoreError::Io {
+                source: e,
+                path: path.clone(),
+            })?
+            .len();
+        let written = async {
+            wal.file.write_all(&lines).await?;
+            wal.file.flush().await?;
+            wal.file.sync_data().await
+        }
+        .await;
+        if let Err(e) = written {
+            // Entries are only replayed up to a torn last line, so a partial write must not stay
+            // in front of the next ones
+            if let Err(e) = wal.file.set_len(len).await {
+                error!(
+                    "Failed to roll back WAL {:?} after a failed append: {:?}",
+                    path, e
+                );
+            }
+            return Err(FileStoreError::Io { source: e, path });
+        }
+        wal.pending_entries += entries.len();
+
+        Ok(())
+    }
+}
+
+async fn load_snapshot(
+    path: &Path,
+) -> Result<(Vec<WordRecord>, Vec<TransitionCount>), FileStoreError> {
+    match fs::read(path).await {
+        Ok(content) => serde_json::from_slice::<StoredSnapshot>(&content)
+            .map(|snapshot| {
+                let (words, transitions) = match snapshot {
+                    StoredSnapshot::Snapshot { words, transitions } => (words, transitions),
+                    StoredSnapshot::Words(words) => (words, Vec::new()),
+                };
+                (
+                    words.into_iter().map(WordRecord::from).collect(),
+                    transitions,
+                )
+            })
+            .map_err(|e| FileStoreError::Corrupted {
+                source: e,
+                path: path.to_path_buf(),
+                line: 1,
+            }),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok((Vec::new(), Vec::new())),
+        Err(e) => Err(FileStoreError::Io {
+            source: e,
+            path: path.to_path_buf(),
+        }),
+    }
+}
+
+async fn replay_wal(memory: &HashmapStore, path: &Path) -> Result<(), FileStoreError> {
+    let content = match fs::read_to_string(path).await {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => {
+            return Err(FileStoreError::Io {
+                source: e,
+                path: path.to_path_buf(),
+            })
+        }
+    };
+
+    let lines: Vec<&str> = content.lines().filter(|line| !line.is_empty()).collect();
+    let mut words = memory.word_store.write().await;
+    for (index, line) in lines.iter().enumerate() {
+        match serde_json::from_str::<WalEntry>(line) {
+            Ok(WalEntry::Add(stored)) | Ok(WalEntry::Update(stored)) => {
+                words.upsert(WordRecord::from(stored));
+            }
+            Ok(WalEntry::Remove(word)) => {
+                words.remove(&word);
+            }
+            Ok(WalEntry::Learn(sentence)) => {
+                words.transitions.learn(&sentence);
+            }
+            // A crash while appending can leave the last entry half-written
+            Err(e) if index == lines.len() - 1 => {
+                warn!("Ignoring truncated last WAL entry in {:?}: {:?}", path, e);
+            }
+            Err(e) => {
+                return Err(FileStoreError::Corrupted {
+                    source: e,
+                    path: path.to_path_buf(),
+                    line: index + 1,
+                })
+            }
+        }
+    }
+    debug!("Replayed {0} WAL entries", lines.len());
+
+    Ok(())
+}
+
+fn map_memory_error(err: StoreError<HashmapStoreError>) -> StoreError<FileStoreError> {
+    match err {
+        StoreError::NotFound(word) => StoreError::NotFound(word),
+        StoreError::AlreadyExists(word) => StoreError::AlreadyExists(word),
+        StoreError::Empty => StoreError::Empty,
+        StoreError::InternalStoreError(e) => {
+            StoreError::InternalStoreError(FileStoreError::Memory(e))
+        }
+    }
+}
+
+#[async_trait]
+impl Store for FileStore {
+    type E = FileStoreError;
+
+    #[tracing::instrument(fields(component = "File Store"), skip(self))]
+    async fn get_word(&self, word: String) -> Result<WordRecord, StoreError<FileStoreError>> {
+        trace!("Getting word {:?} from file store...", word);
+
+        self.memory.get_word(word).await.map_err(map_memory_error)
+    }
+
+    #[tracing::instrument(fields(component = "File Store"), skip(self))]
+    async fn get_random_word(&self) -> Result<String, StoreError<FileStoreError>> {
+        trace!("Getting a random word from file store...");
+
+        self.memory
+            .get_random_word()
+            .await
+            .map_err(map_memory_error)
+    }
+
+    #[tracing::instrument(fields(component = "File Store"), skip(self))]
+    async fn add_word(&mut self, record: WordRecord) -> Result<(), StoreError<FileStoreError>> {
+        trace!("Adding word {:?} to file store...", record.word);
+
+        let mut wal = self.wal.lock().await;
+
+        if self.memory.get_word(record.word.clone()).await.is_ok() {
+            return Err(StoreError::AlreadyExists(record.word));
+        }
+
+        Self::append(
+            &mut wal,
+            self.data_dir.join(WAL_FILE),
+            &[WalEntry::Add(StoredRecord::Record(record.clone()))],
+        )
+        .await
+        .map_err(StoreError::InternalStoreError)?;
+
+        self.memory.add_word(record).await.map_err(map_memory_error)
+    }
+
+    #[tracing::instrument(fields(component = "File Store"), skip(self))]
+    async fn remove_word(&mut self, word: String) -> Result<(), StoreError<FileStoreError>> {
+        trace!("Removing word {:?} from file store...", word);
+
+        let mut wal = self.wal.lock().await;
+
+        if self.memory.get_word(word.clone()).await.is_err() {
+            return Err(StoreError::NotFound(word));
+        }
+
+        Self::append(
+            &mut wal,
+            self.data_dir.join(WAL_FILE),
+            &[WalEntry::Remove(word.clone())],
+        )
+        .await
+        .map_err(StoreError::InternalStoreError)?;
+
+        self.memory
+            .remove_word(word)
+            .await
+            .map_err(map_memory_error)
+    }
+
+    #[tracing::instrument(fields(component = "File Store"), skip(self, records))]
+    async fn add_words(
+        &mut self,
+        records: Vec<WordRecord>,
+    ) -> Result<Vec<AddWordStatus>, StoreError<FileStoreError>> {
+        trace!("Adding {0} words to file store...", records.len());
+
+        let mut wal = self.wal.lock().await;
+
+        let mut new_words = HashSet::new();
+        let mut entries = Vec::new();
+        for record in records.iter() {
+            if !new_words.contains(&record.word)
+                && self.memory.get_word(record.word.clone()).await.is_err()
+            {
+                new_words.insert(record.word.clone());
+                entries.push(WalEntry::Add(StoredRecord::Record(record.clone())));
+            }
+        }
+
+        if !entries.is_empty() {
+            Self::append(&mut wal, self.data_dir.join(WAL_FILE), &entries)
+                .await
+                .map_err(StoreError::InternalStoreError)?;
+        }
+
+        self.memory
+            .add_words(records)
+            .await
+            .map_err(map_memory_error)
+    }
+
+    #[tracing::instrument(fields(component = "File Store"), skip(self))]
+    async fn set_weight(
+        &mut self,
+        word: String,
+        weight: f64,
+    ) -> Result<(), StoreError<FileStoreError>> {
+        trace!("Setting weight of word {:?} in file store...", word);
+
+        let mut wal = self.wal.lock().await;
+
+        let record = self
+            .memory
+            .get_word(word.clone())
+            .await
+            .map_err(map_memory_error)?;
+
+        Self::append(
+            &mut wal,
+            self.data_dir.join(WAL_FILE),
+            &[WalEntry::Update(StoredRecord::Record(WordRecord {
+                weight,
+                ..record
+            }))],
+        )
+        .await
+        .map_err(StoreError::InternalStoreError)?;
+
+        self.memory
+            .set_weight(word, weight)
+            .await
+            .map_err(map_memory_error)
+    }
+
+    #[tracing::instrument(fields(component = "File Store"), skip(self))]
+    async fn learn_sentence(
+        &mut self,
+        sentence: Vec<String>,
+    ) -> Result<(), StoreError<FileStoreError>> {
+        trace!(
+            "Learning sentence of {0} words in file store...",
+            sentence.len()
+        );
+
+        let mut wal = self.wal.lock().await;
+
+        Self::append(
+            &mut wal,
+            self.data_dir.join(WAL_FILE),
+            &[WalEntry::Learn(sentence.clone())],
+        )
+        .await
+        .map_err(StoreError::InternalStoreError)?;
+
+        self.memory
+            .learn_sentence(sentence)
+            .await
+            .map_err(map_memory_error)
+    }
+
+    #[tracing::instrument(fields(component = "File Store"), skip(self))]
+    async fn get_next_word(
+        &self,
+        tail: Vec<String>,
+    ) -> Result<Option<String>, StoreError<FileStoreError>> {
+        trace!("Getting next word of {:?} from file store...", tail);
+
+        self.memory
+            .get_next_word(tail)
+            .await
+            .map_err(map_memory_error)
+    }
+
+    #[tracing::instrument(fields(component = "File Store"), skip(self))]
+    async fn list_words(
+        &self,
+        cursor: Option<String>,
+        limit: usize,
+        prefix: Option<String>,
+    ) -> Result<WordPage, StoreError<FileStoreError>> {
+        trace!("Listing words of file store...");
+
+        self.memory
+            .list_words(cursor, limit, prefix)
+            .await
+            .map_err(map_memory_error)
+    }
+
+    fn iter_words(&self) -> BoxStream<'static, Result<WordRecord, StoreError<FileStoreError>>> {
+        trace!("Iterating over words of file store...");
+
+        self.memory
+            .iter_words()
+            .map(|result| result.map_err(map_memory_error))
+            .boxed()
+    }
+
+    /// Fails when the data directory went away, as the WAL could no longer be compacted.
+    async fn ping(&self) -> Result<(), StoreError<FileStoreError>> {
+        fs::metadata(&self.data_dir).await.map_err(|e| {
+            StoreError::InternalStoreError(FileStoreError::Io {
+                source: e,
+                path: self.data_dir.clone(),
+            })
+        })?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn record(word: &str) -> WordRecord {
+        WordRecord::new(word.to_string(), WordMetadata::default(), "test")
+    }
+
+    /// Fills a store opened in `dir`, every change going to the WAL only.
+    async fn fill(dir: &TempDir) -> FileStore {
+        let mut store = FileStore::open(dir.path()).await.unwrap();
+        store.add_word(record("alpha")).await.unwrap();
+        store
+            .add_words(vec![record("beta"), record("gamma")])
+            .await
+            .unwrap();
+        store.set_weight("beta".to_string(), 4.0).await.unwrap();
+        store.remove_word("gamma".to_string()).await.unwrap();
+        store
+            .learn_sentence(vec!["alpha".to_string(), "beta".to_string()])
+            .await
+            .unwrap();
+        store
+    }
+
+    async fn assert_filled(store: &FileStore) {
+        let page = store.list_words(None, 10, None).await.unwrap();
+        assert_eq!(page.words, vec!["alpha", "beta"]);
+        assert_eq!(
+            store.get_word("beta".to_string()).await.unwrap().weight,
+            4.0
+        );
+        assert_eq!(
+            store
+                .get_next_word(vec!["alpha".to_string()])
+                .await
+                .unwrap(),
+            Some("beta".to_string())
+        );
+    }
+
+    async fn file_len(dir: &TempDir, file: &str) -> u64 {
+        fs::metadata(dir.path().join(file)).await.unwrap().len()
+    }
+
+    #[tokio::test]
+    async fn reopening_replays_the_wal() {
+        let dir = TempDir::new().unwrap();
+        drop(fill(&dir).await);
+        assert!(file_len(&dir, WAL_FILE).await > 0);
+
+        let store = FileStore::open(dir.path()).await.unwrap();
+        assert_filled(&store).await;
+        // Whatever was replayed is compacted right away
+        assert_eq!(file_len(&dir, WAL_FILE).await, 0);
+    }
+
+    #[tokio::test]
+    async fn snapshots_truncate_the_wal() {
+        let dir = TempDir::new().unwrap();
+        let store = fill(&dir).await;
+        store.snapshot().await.unwrap();
+        assert_eq!(file_len(&dir, WAL_FILE).await, 0);
+        assert!(!dir.path().join(SNAPSHOT_TMP_FILE).exists());
+        drop(store);
+
+        let store = FileStore::open(dir.path()).await.unwrap();
+        assert_filled(&store).await;
+    }
+
+    #[tokio::test]
+    async fn a_torn_last_wal_entry_is_ignored() {
+        let dir = TempDir::new().unwrap();
+        drop(fill(&dir).await);
+        let mut wal = OpenOptions::new()
+            .append(true)
+            .open(dir.path().join(WAL_FILE))
+            .await
+            .unwrap();
+        wal.write_all(br#"{"op":"add","word":{"wo"#).await.unwrap();
+        drop(wal);
+
+        let store = FileStore::open(dir.path()).await.unwrap();
+        assert_filled(&store).await;
+    }
+
+    #[tokio::test]
+    async fn a_corrupted_wal_entry_before_the_last_one_fails_opening() {
+        let dir = TempDir::new().unwrap();
+        drop(fill(&dir).await);
+        let mut wal = OpenOptions::new()
+            .append(true)
+            .open(dir.path().join(WAL_FILE))
+            .await
+            .unwrap();
+        wal.write_all(b"not json\n{\"op\":\"remove\",\"word\":\"alpha\"}\n")
+            .await
+            .unwrap();
+        drop(wal);
+
+        assert!(matches!(
+            FileStore::open(dir.path()).await,
+            Err(FileStoreError::Corrupted { .. })
+        ));
+    }
+}
@@ -1,6 +1,9 @@
-use crate::stores::{Store, StoreError};
-use rand::Rng;
-use std::collections::HashMap;
+use crate::stores::markov::{TransitionCount, Transitions};
+use crate::stores::weighted::WeightedIndex;
+use crate::stores::{AddWordStatus, Store, StoreError, WordPage, WordRecord};
+use futures::stream::{self, BoxStream, StreamExt};
+use std::collections::{BTreeSet, HashMap};
+use std::ops::Bound;
 use std::sync::Arc;
 use thiserror::Error;
 use tokio::sync::RwLock;
@@ -8,48 +11,90 @@ use tonic::async_trait;
 use tracing::trace;
 
 #[derive(Error, Debug)]
-pub enum HashmapStoreError {
-    #[error("Wrong index generation during random picking")]
-    WrongIndexGeneration,
+pub enum HashmapStoreError {}
+
+/// Records of the store along with the index used to pick them at random,
+/// kept behind a single lock so that both always agree.
+#[derive(Debug, Default)]
+pub struct Words {
+    records: HashMap<String, WordRecord>,
+    index: WeightedIndex,
+    /// Words in order, for listing pages without sorting the whole store
+    sorted: BTreeSet<String>,
+    pub transitions: Transitions,
+}
+
+impl Words {
+    pub fn get(&self, word: &str) -> Option<&WordRecord> {
+        self.records.get(word)
+    }
+
+    /// Inserts `record` unless its word is already present, returning whether it was inserted.
+    pub fn insert(&mut self, record: WordRecord) -> bool {
+        if self.records.contains_key(&record.word) {
+            return false;
+        }
+        self.upsert(record);
+        true
+    }
+
+    /// Inserts `record`, replacing any previous record of the same word.
+    pub fn upsert(&mut self, record: WordRecord) {
+        self.index.insert(record.word.clone(), record.weight);
+        self.sorted.insert(record.word.clone());
+        self.records.insert(record.word.clone(), record);
+    }
+
+    pub fn remove(&mut self, word: &str) -> Option<WordRecord> {
+        self.index.remove(word);
+        self.sorted.remove(word);
+        self.records.remove(word)
+    }
+
+    pub fn set_weight(&mut self, word: &str, weight: f64) -> bool {
+        match self.records.get_mut(word) {
+            Some(record) => {
+                record.weight = weight;
+                self.index.set_weight(word, weight)
+            }
+            None => false,
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
 pub struct HashmapStore {
-    pub word_store: Arc<RwLock<HashMap<String, String>>>,
+    pub word_store: Arc<RwLock<Words>>,
 }
 
 impl HashmapStore {
     pub async fn new() -> Result<HashmapStore, HashmapStoreError> {
-        let initial_store = Arc::new(RwLock::new(HashMap::new()));
+        Ok(HashmapStore::from_records(Vec::new()))
+    }
 
-        initial_store
-            .write()
-            .await
-            .insert("hello".to_string(), "hello".to_string());
-        initial_store
-            .write()
-            .await
-            .insert("world".to_string(), "world".to_string());
-        initial_store
-            .write()
-            .await
-            .insert("how".to_string(), "how".to_string());
-        initial_store
-            .write()
-            .await
-            .insert("are".to_string(), "are".to_string());
-        initial_store
-            .write()
-            .await
-            .insert("you".to_string(), "you".to_string());
-        initial_store
-            .write()
+    pub fn from_records(records: impl IntoIterator<Item = WordRecord>) -> HashmapStore {
+        let mut initial_store = Words::default();
+        for record in records {
+            initial_store.upsert(record);
+        }
+
+        HashmapStore {
+            word_store: Arc::new(RwLock::new(initial_store)),
+        }
+    }
+
+    pub async fn records(&self) -> Vec<WordRecord> {
+        self.word_store
+            .read()
             .await
-            .insert("?".to_string(), "?".to_string());
+            .records
+            .values()
+            .cloned()
+            .collect()
+    }
 
-        Ok(HashmapStore {
-            word_store: initial_store,
-        })
+    pub async fn transition_counts(&self) -> Vec<TransitionCount> {
+        self.word_store.read().await.transitions.counts()
     }
 }
 
@@ -58,7 +103,7 @@ impl Store for HashmapStore {
     type E = HashmapStoreError;
 
     #[tracing::instrument(fields(component = "Hashmap Store"), skip(self))]
-    async fn get_word(&self, word: String) -> Result<String, StoreError<HashmapStoreError>> {
+    async fn get_word(&self, word: String) -> Result<WordRecord, StoreError<HashmapStoreError>> {
         trace!("Getting word {:?} from hashmap store...", word);
 
         Ok(self
@@ -67,42 +112,32 @@ impl Store for HashmapStore {
             .await
             .get(&word)
             .ok_or(StoreError::NotFound(word))?
-            .to_string())
+            .clone())
     }
 
     #[tracing::instrument(fields(component = "Hashmap Store"), skip(self))]
     async fn get_random_word(&self) -> Result<String, StoreError<HashmapStoreError>> {
         trace!("Getting a random word from hashmap store...");
 
-        if self.word_store.read().await.is_empty() {
-            return Err(StoreError::Empty);
-        }
-
-        let keys: Vec<_> = self.word_store.read().await.keys().cloned().collect();
-        let index = rand::rng().random_range(0..keys.len());
-
         Ok(self
             .word_store
             .read()
             .await
-            .keys()
-            .nth(index)
-            .ok_or(StoreError::InternalStoreError(
-                HashmapStoreError::WrongIndexGeneration,
-            ))?
+            .index
+            .sample(&mut rand::rng())
+            .ok_or(StoreError::Empty)?
             .to_string())
     }
 
     #[tracing::instrument(fields(component = "Hashmap Store"), skip(self))]
-    async fn add_word(&mut self, word: String) -> Result<(), StoreError<HashmapStoreError>> {
-        trace!("Adding word {:?} to hashmap store...", word);
+    async fn add_word(&mut self, record: WordRecord) -> Result<(), StoreError<HashmapStoreError>> {
+        trace!("Adding word {:?} to hashmap store...", record.word);
 
-        if self.word_store.read().await.get(&word).is_some() {
+        let word = record.word.clone();
+        if !self.word_store.write().await.insert(record) {
             return Err(StoreError::AlreadyExists(word));
         }
 
-        self.word_store.write().await.insert(word.clone(), word);
-
         Ok(())
     }
 
@@ -110,12 +145,152 @@ impl Store for HashmapStore {
     async fn remove_word(&mut self, word: String) -> Result<(), StoreError<HashmapStoreError>> {
         trace!("Removing word {:?} from hashmap store...", word);
 
-        if self.word_store.read().await.get(&word).is_none() {
+        if self.word_store.write().await.remove(&word).is_none() {
             return Err(StoreError::NotFound(word));
         }
 
-        self.word_store.write().await.remove(&word);
+        Ok(())
+    }
+
+    #[tracing::instrument(fields(component = "Hashmap Store"), skip(self, records))]
+    async fn add_words(
+        &mut self,
+        records: Vec<WordRecord>,
+    ) -> Result<Vec<AddWordStatus>, StoreError<HashmapStoreError>> {
+        trace!("Adding {0} words to hashmap store...", records.len());
+
+        let mut word_store = self.word_store.write().await;
 
+        Ok(records
+            .into_iter()
+            .map(|record| match word_store.insert(record) {
+                true => AddWordStatus::Created,
+                false => AddWordStatus::AlreadyExists,
+            })
+            .collect())
+    }
+
+    #[tracing::instrument(fields(component = "Hashmap Store"), skip(self))]
+    async fn set_weight(
+        &mut self,
+        word: String,
+        weight: f64,
+    ) -> Result<(), StoreError<HashmapStoreError>> {
+        trace!("Setting weight of word {:?} in hashmap store...", word);
+
+        if !self.word_store.write().await.set_weight(&word, weight) {
+            return Err(StoreError::NotFound(word));
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(fields(component = "Hashmap Store"), skip(self))]
+    async fn learn_sentence(
+        &mut self,
+        sentence: Vec<String>,
+    ) -> Result<(), StoreError<HashmapStoreError>> {
+        trace!(
+            "Learning sentence of {0} words in hashmap store...",
+            sentence.len()
+        );
+
+        self.word_store.write().await.transitions.learn(&sentence);
+
+        Ok(())
+    }
+
+    #[tracing::instrument(fields(component = "Hashmap Store"), skip(self))]
+    async fn get_next_word(
+        &self,
+        tail: Vec<String>,
+    ) -> Result<Option<String>, StoreError<HashmapStoreError>> {
+        trace!("Getting next word of {:?} from hashmap store...", tail);
+
+        Ok(self
+            .word_store
+            .read()
+            .await
+            .transitions
+            .next_word(&tail, &mut rand::rng()))
+    }
+
+    #[tracing::instrument(fields(component = "Hashmap Store"), skip(self))]
+    async fn list_words(
+        &self,
+        cursor: Option<String>,
+        limit: usize,
+        prefix: Option<String>,
+    ) -> Result<WordPage, StoreError<HashmapStoreError>> {
+        trace!("Listing words of hashmap store...");
+
+        let prefix = prefix.unwrap_or_default();
+        // Words starting with the prefix sort right from it
+        let start = match cursor {
+            Some(cursor) if cursor >= prefix => Bound::Excluded(cursor),
+            _ => Bound::Included(prefix.clone()),
+        };
+        let words: Vec<String> = self
+            .word_store
+            .read()
+            .await
+            .sorted
+            .range((start, Bound::Unbounded))
+            .take_while(|word| word.starts_with(&prefix))
+            .take(limit + 1)
+            .cloned()
+            .collect();
+
+        Ok(WordPage::from_sorted(words, limit))
+    }
+
+    fn iter_words(&self) -> BoxStream<'static, Result<WordRecord, StoreError<HashmapStoreError>>> {
+        trace!("Iterating over words of hashmap store...");
+
+        let store = self.clone();
+        stream::once(async move { store.records().await })
+            .flat_map(|records| stream::iter(records.into_iter().map(Ok)))
+            .boxed()
+    }
+
+    async fn ping(&self) -> Result<(), StoreError<HashmapStoreError>> {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stores::WordMetadata;
+
+    #[tokio::test]
+    async fn list_words_pages_through_the_words_with_a_prefix() {
+        let store = HashmapStore::from_records(
+            ["beta", "apple", "apricot", "avocado", "ant", "banana"]
+                .map(|word| WordRecord::new(word.to_string(), WordMetadata::default(), "test")),
+        );
+
+        let page = store
+            .list_words(None, 2, Some("ap".to_string()))
+            .await
+            .unwrap();
+        assert_eq!(page.words, vec!["apple", "apricot"]);
+        assert_eq!(page.next_cursor, None);
+
+        let page = store.list_words(None, 2, None).await.unwrap();
+        assert_eq!(page.words, vec!["ant", "apple"]);
+        let page = store
+            .list_words(page.next_cursor, 2, Some("a".to_string()))
+            .await
+            .unwrap();
+        assert_eq!(page.words, vec!["apricot", "avocado"]);
+        assert_eq!(page.next_cursor, None);
+
+        // A cursor sorting before the prefix starts from the prefix
+        let page = store
+            .list_words(Some("a".to_string()), 5, Some("b".to_string()))
+            .await
+            .unwrap();
+        assert_eq!(page.words, vec!["banana", "beta"]);
+    }
+}
@@ -0,0 +1,172 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Number of preceding words a transition is conditioned on at most, making
+/// the model learn both bigrams and trigrams.
+pub const MAX_CONTEXT_LENGTH: usize = 2;
+
+/// How many times `next` was seen right after the words of `context`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct TransitionCount {
+    pub context: Vec<String>,
+    pub next: String,
+    pub count: u64,
+}
+
+/// Yields every `(context, next)` pair of `sentence`, for each context length up to [`MAX_CONTEXT_LENGTH`].
+pub fn transitions(sentence: &[String]) -> impl Iterator<Item = (&[String], &String)> {
+    (1..sentence.len()).flat_map(move |position| {
+        (1..=MAX_CONTEXT_LENGTH.min(position))
+            .map(move |length| (&sentence[position - length..position], &sentence[position]))
+    })
+}
+
+/// Yields the contexts to look up for a chain ending with `tail`, longest first.
+pub fn tail_contexts(tail: &[String]) -> impl Iterator<Item = &[String]> {
+    (1..=MAX_CONTEXT_LENGTH.min(tail.len()))
+        .rev()
+        .map(move |length| &tail[tail.len() - length..])
+}
+
+/// Picks one of `candidates` with a probability proportional to its count.
+pub fn pick_weighted<'a>(
+    candidates: impl IntoIterator<Item = (&'a String, u64)> + Clone,
+    rng: &mut impl Rng,
+) -> Option<&'a String> {
+    let total: u64 = candidates.clone().into_iter().map(|(_, count)| count).sum();
+    if total == 0 {
+        return None;
+    }
+
+    let mut target = rng.random_range(0..total);
+    for (word, count) in candidates {
+        if target < count {
+            return Some(word);
+        }
+        target -= count;
+    }
+    None
+}
+
+/// In-memory transition counts of a Markov chain over words.
+#[derive(Clone, Debug, Default)]
+pub struct Transitions {
+    counts: HashMap<Vec<String>, HashMap<String, u64>>,
+}
+
+impl Transitions {
+    pub fn learn(&mut self, sentence: &[String]) {
+        for (context, next) in transitions(sentence) {
+            self.add(context.to_vec(), next.clone(), 1);
+        }
+    }
+
+    pub fn add(&mut self, context: Vec<String>, next: String, count: u64) {
+        *self
+            .counts
+            .entry(context)
+            .or_default()
+            .entry(next)
+            .or_default() += count;
+    }
+
+    /// Picks the word following `tail`, conditioned on the longest context that was learned.
+    pub fn next_word(&self, tail: &[String], rng: &mut impl Rng) -> Option<String> {
+        tail_contexts(tail)
+            .find_map(|context| self.counts.get(context))
+            .and_then(|successors| {
+                pick_weighted(successors.iter().map(|(word, count)| (word, *count)), rng)
+            })
+            .cloned()
+    }
+
+    pub fn counts(&self) -> Vec<TransitionCount> {
+        self.counts
+            .iter()
+            .flat_map(|(context, successors)| {
+                successors.iter().map(|(next, count)| TransitionCount {
+                    context: context.clone(),
+                    next: next.clone(),
+                    count: *count,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn words(words: &[&str]) -> Vec<String> {
+        words.iter().map(|word| word.to_string()).collect()
+    }
+
+    fn transition(context: &[&str], next: &str, count: u64) -> TransitionCount {
+        TransitionCount {
+            context: words(context),
+            next: next.to_string(),
+            count,
+        }
+    }
+
+    #[test]
+    fn learning_records_bigrams_and_trigrams() {
+        let mut transitions = Transitions::default();
+        transitions.learn(&words(&["the", "cat", "sat"]));
+        transitions.learn(&words(&["the", "cat", "ran"]));
+
+        let mut counts = transitions.counts();
+        counts.sort_by(|a, b| (&a.context, &a.next).cmp(&(&b.context, &b.next)));
+        assert_eq!(
+            counts,
+            vec![
+                transition(&["cat"], "ran", 1),
+                transition(&["cat"], "sat", 1),
+                transition(&["the"], "cat", 2),
+                transition(&["the", "cat"], "ran", 1),
+                transition(&["the", "cat"], "sat", 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn next_word_follows_the_longest_learned_context() {
+        let mut transitions = Transitions::default();
+        transitions.learn(&words(&["a", "dog", "barked"]));
+        transitions.learn(&words(&["the", "dog", "slept"]));
+        let mut rng = StdRng::seed_from_u64(7);
+
+        for _ in 0..20 {
+            assert_eq!(
+                transitions.next_word(&words(&["a", "dog"]), &mut rng),
+                Some("barked".to_string())
+            );
+        }
+        // Without a trigram, the bigram of the last word decides
+        let next = transitions.next_word(&words(&["my", "dog"]), &mut rng);
+        assert!(matches!(next.as_deref(), Some("barked" | "slept")));
+        assert_eq!(transitions.next_word(&words(&["cat"]), &mut rng), None);
+    }
+
+    #[test]
+    fn next_word_is_picked_according_to_the_counts() {
+        let mut transitions = Transitions::default();
+        transitions.add(words(&["go"]), "left".to_string(), 1);
+        transitions.add(words(&["go"]), "right".to_string(), 3);
+        let mut rng = StdRng::seed_from_u64(7);
+
+        let rights = (0..4000)
+            .filter(|_| {
+                transitions.next_word(&words(&["go"]), &mut rng).as_deref() == Some("right")
+            })
+            .count();
+        assert!(
+            (2800..3200).contains(&rights),
+            "right was picked {rights} times"
+        );
+    }
+}
@@ -1,8 +1,17 @@
+use futures::stream::BoxStream;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::time::{SystemTime, UNIX_EPOCH};
 use std::{error::Error, fmt::Debug};
 use thiserror::Error;
 use tonic::async_trait;
 
+pub mod file;
 pub mod hashmap;
+pub mod markov;
+pub mod seed;
+pub mod sqlite;
+pub mod weighted;
 
 #[derive(Error, Debug)]
 pub enum StoreError<E: Error> {
@@ -16,12 +25,125 @@ pub enum StoreError<E: Error> {
     InternalStoreError(#[source] E),
 }
 
+/// Optional metadata that can be attached to a word when it is added.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct WordMetadata {
+    pub origin: Option<String>,
+    pub language: Option<String>,
+    pub weight: Option<f64>,
+    #[serde(default)]
+    pub labels: BTreeMap<String, String>,
+}
+
+/// A word along with the metadata persisted by the store.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct WordRecord {
+    pub word: String,
+    /// Creation time in milliseconds since the Unix epoch
+    #[serde(default)]
+    pub created_at: u64,
+    /// Identifier of the service that created the word
+    #[serde(default)]
+    pub origin: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    #[serde(default = "default_weight")]
+    pub weight: f64,
+    #[serde(default)]
+    pub labels: BTreeMap<String, String>,
+}
+
+pub const DEFAULT_WEIGHT: f64 = 1.0;
+
+fn default_weight() -> f64 {
+    DEFAULT_WEIGHT
+}
+
+/// Weights must be finite and non-negative, a weight of zero excluding the word from random picks.
+pub fn is_valid_weight(weight: f64) -> bool {
+    weight.is_finite() && weight >= 0.0
+}
+
+impl WordRecord {
+    /// Creates a record timestamped now, `default_origin` being used when the metadata has none.
+    pub fn new(word: String, metadata: WordMetadata, default_origin: &str) -> Self {
+        WordRecord {
+            word,
+            created_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|duration| duration.as_millis() as u64)
+                .unwrap_or_default(),
+            origin: metadata
+                .origin
+                .unwrap_or_else(|| default_origin.to_string()),
+            language: metadata.language,
+            weight: metadata.weight.unwrap_or(DEFAULT_WEIGHT),
+            labels: metadata.labels,
+        }
+    }
+}
+
+/// Outcome of adding a single word as part of a batch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddWordStatus {
+    Created,
+    AlreadyExists,
+}
+
+/// A page of words in lexicographic order. `next_cursor` is set when more
+/// words follow and must be passed back to fetch the next page.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WordPage {
+    pub words: Vec<String>,
+    pub next_cursor: Option<String>,
+}
+
+impl WordPage {
+    /// Builds a page out of up to `limit + 1` sorted words, the extra word only
+    /// signalling that another page follows.
+    pub fn from_sorted(mut words: Vec<String>, limit: usize) -> Self {
+        let next_cursor = if words.len() > limit {
+            words.truncate(limit);
+            words.last().cloned()
+        } else {
+            None
+        };
+        WordPage { words, next_cursor }
+    }
+}
+
 #[async_trait]
 pub trait Store: Send + Sync + 'static + Clone + Debug {
-    type E: Error;
+    type E: Error + Send + Sync + 'static;
 
-    async fn get_word(&self, word: String) -> Result<String, StoreError<Self::E>>;
+    async fn get_word(&self, word: String) -> Result<WordRecord, StoreError<Self::E>>;
+    /// Picks a random word with a probability proportional to its weight, failing
+    /// with [`StoreError::Empty`] when no word has a positive weight.
     async fn get_random_word(&self) -> Result<String, StoreError<Self::E>>;
-    async fn add_word(&mut self, word: String) -> Result<(), StoreError<Self::E>>;
+    async fn add_word(&mut self, record: WordRecord) -> Result<(), StoreError<Self::E>>;
     async fn remove_word(&mut self, word: String) -> Result<(), StoreError<Self::E>>;
+    /// Adds every word of the batch, reporting for each one whether it was created.
+    async fn add_words(
+        &mut self,
+        records: Vec<WordRecord>,
+    ) -> Result<Vec<AddWordStatus>, StoreError<Self::E>>;
+    /// Replaces the weight biasing random picks of `word`.
+    async fn set_weight(&mut self, word: String, weight: f64) -> Result<(), StoreError<Self::E>>;
+    /// Counts the transitions between the consecutive words of `sentence`.
+    async fn learn_sentence(&mut self, sentence: Vec<String>) -> Result<(), StoreError<Self::E>>;
+    /// Picks a word following the last words of `tail` according to the learned
+    /// transitions, or `None` when nothing was learned after them.
+    async fn get_next_word(&self, tail: Vec<String>)
+        -> Result<Option<String>, StoreError<Self::E>>;
+    /// Lists up to `limit` words sorting strictly after `cursor` and starting with `prefix`.
+    async fn list_words(
+        &self,
+        cursor: Option<String>,
+        limit: usize,
+        prefix: Option<String>,
+    ) -> Result<WordPage, StoreError<Self::E>>;
+    /// Streams every word of the store, in no particular order.
+    fn iter_words(&self) -> BoxStream<'static, Result<WordRecord, StoreError<Self::E>>>;
+    /// Checks that the store can still serve requests.
+    async fn ping(&self) -> Result<(), StoreError<Self::E>>;
 }
@@ -0,0 +1,208 @@
+use crate::stores::{AddWordStatus, Store, WordMetadata, WordRecord};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+use tracing::info;
+
+pub const DEFAULT_WORDS: [&str; 6] = ["hello", "world", "how", "are", "you", "?"];
+
+#[derive(Error, Debug)]
+pub enum SeedError {
+    #[error("Failed to read seed file {path:?}")]
+    Io {
+        #[source]
+        source: std::io::Error,
+        path: PathBuf,
+    },
+    #[error("Seed file {path:?} is not a JSON array of strings")]
+    InvalidJson {
+        #[source]
+        source: serde_json::Error,
+        path: PathBuf,
+    },
+    #[error("A seed path is required for seed source {0:?}")]
+    MissingPath(SeedSource),
+    #[error("Failed to seed store")]
+    StoreError(#[source] Box<dyn std::error::Error + Send + Sync>),
+}
+
+/// Where the initial vocabulary of a store comes from.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SeedSource {
+    /// The built-in [`DEFAULT_WORDS`]
+    Default,
+    /// No seeding at all
+    Empty,
+    /// A file with one word per line
+    Lines,
+    /// A file containing a JSON array of words
+    Json,
+}
+
+pub async fn load_seed(source: SeedSource, path: &str) -> Result<Vec<String>, SeedError> {
+    match source {
+        SeedSource::Default => Ok(DEFAULT_WORDS.iter().map(|word| word.to_string()).collect()),
+        SeedSource::Empty => Ok(Vec::new()),
+        SeedSource::Lines => {
+            let content = read_seed_file(source, path).await?;
+            Ok(content
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty())
+                .map(str::to_string)
+                .collect())
+        }
+        SeedSource::Json => {
+            let content = read_seed_file(source, path).await?;
+            serde_json::from_str(&content).map_err(|e| SeedError::InvalidJson {
+                source: e,
+                path: PathBuf::from(path),
+            })
+        }
+    }
+}
+
+async fn read_seed_file(source: SeedSource, path: &str) -> Result<String, SeedError> {
+    if path.is_empty() {
+        return Err(SeedError::MissingPath(source));
+    }
+
+    tokio::fs::read_to_string(Path::new(path))
+        .await
+        .map_err(|e| SeedError::Io {
+            source: e,
+            path: PathBuf::from(path),
+        })
+}
+
+/// Adds `words` to `store` on behalf of `origin`, skipping those that already exist.
+/// When `only_if_empty` is set, a store that already contains data is left untouched.
+///
+/// Returns the number of words that were added.
+pub async fn seed_store<S: Store>(
+    store: &mut S,
+    words: Vec<String>,
+    only_if_empty: bool,
+    origin: &str,
+) -> Result<usize, SeedError> {
+    if only_if_empty {
+        // Random picks would also fail as empty when every weight is 0
+        let page = store
+            .list_words(None, 1, None)
+            .await
+            .map_err(|e| SeedError::StoreError(Box::new(e)))?;
+        if !page.words.is_empty() {
+            info!("Store already contains data, skipping seeding");
+            return Ok(0);
+        }
+    }
+
+    let records = words
+        .into_iter()
+        .map(|word| WordRecord::new(word, WordMetadata::default(), origin))
+        .collect();
+
+    let added = store
+        .add_words(records)
+        .await
+        .map_err(|e| SeedError::StoreError(Box::new(e)))?
+        .into_iter()
+        .filter(|status| *status == AddWordStatus::Created)
+        .count();
+
+    info!("Seeded store with {0} words", added);
+
+    Ok(added)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stores::hashmap::HashmapStore;
+    use tempfile::TempDir;
+
+    async fn write_seed(dir: &TempDir, content: &str) -> String {
+        let path = dir.path().join("seed");
+        tokio::fs::write(&path, content).await.unwrap();
+        path.to_str().unwrap().to_string()
+    }
+
+    #[tokio::test]
+    async fn lines_seeds_skip_blank_lines() {
+        let dir = TempDir::new().unwrap();
+        let path = write_seed(&dir, "hello\n  world \n\n?\n").await;
+
+        let words = load_seed(SeedSource::Lines, &path).await.unwrap();
+        assert_eq!(words, vec!["hello", "world", "?"]);
+    }
+
+    #[tokio::test]
+    async fn json_seeds_are_arrays_of_words() {
+        let dir = TempDir::new().unwrap();
+        let path = write_seed(&dir, r#"["hello", "world"]"#).await;
+        let words = load_seed(SeedSource::Json, &path).await.unwrap();
+        assert_eq!(words, vec!["hello", "world"]);
+
+        let path = write_seed(&dir, r#"{"words": ["hello"]}"#).await;
+        assert!(matches!(
+            load_seed(SeedSource::Json, &path).await,
+            Err(SeedError::InvalidJson { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn seed_files_must_exist() {
+        assert!(matches!(
+            load_seed(SeedSource::Lines, "").await,
+            Err(SeedError::MissingPath(SeedSource::Lines))
+        ));
+
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("missing");
+        assert!(matches!(
+            load_seed(SeedSource::Json, path.to_str().unwrap()).await,
+            Err(SeedError::Io { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn only_empty_stores_are_seeded_when_asked() {
+        let words = vec!["hello".to_string(), "world".to_string()];
+
+        let mut store = HashmapStore::new().await.unwrap();
+        assert_eq!(
+            seed_store(&mut store, words.clone(), true, "seed")
+                .await
+                .unwrap(),
+            2
+        );
+        assert_eq!(
+            store.get_word("hello".to_string()).await.unwrap().origin,
+            "seed"
+        );
+
+        let mut store = HashmapStore::from_records([WordRecord::new(
+            "hello".to_string(),
+            WordMetadata::default(),
+            "test",
+        )]);
+        assert_eq!(
+            seed_store(&mut store, words.clone(), true, "seed")
+                .await
+                .unwrap(),
+            0
+        );
+        assert!(store.get_word("world".to_string()).await.is_err());
+
+        // Existing words are kept as they are otherwise
+        assert_eq!(
+            seed_store(&mut store, words, false, "seed").await.unwrap(),
+            1
+        );
+        assert_eq!(
+            store.get_word("hello".to_string()).await.unwrap().origin,
+            "test"
+        );
+    }
+}
@@ -0,0 +1,640 @@
+use crate::stores::markov::{pick_weighted, tail_contexts, transitions};
+use crate::stores::weighted::WeightedIndex;
+use crate::stores::{AddWordStatus, Store, StoreError, WordPage, WordRecord};
+use futures::stream::{self, BoxStream, StreamExt};
+use rusqlite::types::Type;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use std::path::Path;
+use std::sync::{Arc, Mutex, RwLock};
+use thiserror::Error;
+use tonic::async_trait;
+use tracing::{debug, info, trace};
+
+const ITER_PAGE_SIZE: usize = 500;
+
+/// Schema migrations, applied in order. The index of a migration + 1 is the
+/// schema version stored in `PRAGMA user_version` once it has been applied.
+const MIGRATIONS: &[&str] = &[
+    "CREATE TABLE words (word TEXT PRIMARY KEY NOT NULL);",
+    "ALTER TABLE words ADD COLUMN created_at INTEGER NOT NULL DEFAULT 0;
+     ALTER TABLE words ADD COLUMN origin TEXT NOT NULL DEFAULT '';
+     ALTER TABLE words ADD COLUMN language TEXT;
+     ALTER TABLE words ADD COLUMN weight REAL NOT NULL DEFAULT 1.0;
+     ALTER TABLE words ADD COLUMN labels TEXT NOT NULL DEFAULT '{}';",
+    "CREATE TABLE transitions (
+         context TEXT NOT NULL,
+         next TEXT NOT NULL,
+         count INTEGER NOT NULL,
+         PRIMARY KEY (context, next)
+     );",
+];
+
+const RECORD_COLUMNS: &str = "word, created_at, origin, language, weight, labels";
+
+const INSERT_RECORD: &str = "INSERT OR IGNORE INTO words \
+     (word, created_at, origin, language, weight, labels) \
+     VALUES (?1, ?2, ?3, ?4, ?5, ?6)";
+
+#[derive(Error, Debug)]
+pub enum SqliteStoreError {
+    #[error("SQLite error")]
+    Sqlite(#[source] rusqlite::Error),
+    #[error("Failed to apply migration to schema version {version}")]
+    Migration {
+        #[source]
+        source: rusqlite::Error,
+        version: usize,
+    },
+    #[error("Database schema version {0} is newer than supported")]
+    UnsupportedSchemaVersion(usize),
+    #[error("Failed to serialize word labels or transition context")]
+    Serialization(#[source] serde_json::Error),
+    #[error("Database connection lock is poisoned")]
+    PoisonedLock,
+    #[error("Blocking database task failed")]
+    TaskError(#[source] tokio::task::JoinError),
+}
+
+/// Store persisting words in a SQLite database. Weights are mirrored in an
+/// in-memory index for random picks, only updated while holding the
+/// connection so that it follows the committed state of the table.
+#[derive(Clone, Debug)]
+pub struct SqliteStore {
+    connection: Arc<Mutex<Connection>>,
+    index: Arc<RwLock<WeightedIndex>>,
+}
+
+impl SqliteStore {
+    pub async fn open(path: impl AsRef<Path>) -> Result<SqliteStore, SqliteStoreError> {
+        let path = path.as_ref().to_path_buf();
+
+        let (connection, index) = tokio::task::spawn_blocking(move || {
+            let mut connection = Connection::open(&path).map_err(SqliteStoreError::Sqlite)?;
+            connection
+                .pragma_update(None, "journal_mode", "WAL")
+                .map_err(SqliteStoreError::Sqlite)?;
+
+            let previous_version = migrate(&mut connection)?;
+            if previous_version == 0 {
+                info!("Initialized new database at {:?}", path);
+            }
+
+            let index = load_index(&connection).map_err(SqliteStoreError::Sqlite)?;
+
+            Ok((connection, index))
+        })
+        .await
+        .map_err(SqliteStoreError::TaskError)??;
+
+        Ok(SqliteStore {
+            connection: Arc::new(Mutex::new(connection)),
+            index: Arc::new(RwLock::new(index)),
+        })
+    }
+
+    /// Applies `f` to the weighted index. Callers mutating it must hold the connection lock.
+    fn with_index<T>(
+        index: &RwLock<WeightedIndex>,
+        f: impl FnOnce(&mut WeightedIndex) -> T,
+    ) -> Result<T, StoreError<SqliteStoreError>> {
+        let mut index = index
+            .write()
+            .map_err(|_| StoreError::InternalStoreError(SqliteStoreError::PoisonedLock))?;
+        Ok(f(&mut index))
+    }
+
+    async fn with_connection<T, F>(&self, f: F) -> Result<T, StoreError<SqliteStoreError>>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Connection) -> Result<T, StoreError<SqliteStoreError>> + Send + 'static,
+    {
+        let connection = self.connection.clone();
+        tokio::task::spawn_blocking(move || {
+            let connection = connection
+                .lock()
+                .map_err(|_| StoreError::InternalStoreError(SqliteStoreError::PoisonedLock))?;
+            f(&connection)
+        })
+        .await
+        .map_err(|e| StoreError::InternalStoreError(SqliteStoreError::TaskError(e)))?
+    }
+}
+
+/// Applies pending migrations and returns the schema version found before migrating.
+fn migrate(connection: &mut Connection) -> Result<usize, SqliteStoreError> {
+    let current_version: usize = connection
+        .pragma_query_value(None, "user_version", |row| row.get(0))
+        .map_err(SqliteStoreError::Sqlite)?;
+
+    if current_version > MIGRATIONS.len() {
+        return Err(SqliteStoreError::UnsupportedSchemaVersion(current_version));
+    }
+
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(current_version) {
+        let version = index + 1;
+        debug!("Applying migration to schema version {0}...", version);
+
+        let transaction = connection
+            .transaction()
+            .map_err(|e| SqliteStoreError::Migration { source: e, version })?;
+        transaction
+            .execute_batch(migration)
+            .and_then(|_| transaction.pragma_update(None, "user_version", version))
+            .and_then(|_| transaction.commit())
+            .map_err(|e| SqliteStoreError::Migration { source: e, version })?;
+    }
+
+    Ok(current_version)
+}
+
+fn load_index(connection: &Connection) -> rusqlite::Result<WeightedIndex> {
+    let mut index = WeightedIndex::new();
+    let mut statement = connection.prepare("SELECT word, weight FROM words")?;
+    let mut rows = statement.query([])?;
+    while let Some(row) = rows.next()? {
+        index.insert(row.get(0)?, row.get(1)?);
+    }
+    Ok(index)
+}
+
+fn internal_error(err: rusqlite::Error) -> StoreError<SqliteStoreError> {
+    StoreError::InternalStoreError(SqliteStoreError::Sqlite(err))
+}
+
+/// Transition contexts are stored as JSON arrays of words.
+fn context_key(context: &[String]) -> Result<String, StoreError<SqliteStoreError>> {
+    serde_json::to_string(context)
+        .map_err(|e| StoreError::InternalStoreError(SqliteStoreError::Serialization(e)))
+}
+
+fn read_record(row: &Row) -> rusqlite::Result<WordRecord> {
+    let labels: String = row.get(5)?;
+    Ok(WordRecord {
+        word: row.get(0)?,
+        created_at: row.get(1)?,
+        origin: row.get(2)?,
+        language: row.get(3)?,
+        weight: row.get(4)?,
+        labels: serde_json::from_str(&labels)
+            .map_err(|e| rusqlite::Error::FromSqlConversionFailure(5, Type::Text, Box::new(e)))?,
+    })
+}
+
+fn insert_record(
+    statement: &mut rusqlite::CachedStatement,
+    record: &WordRecord,
+) -> Result<usize, StoreError<SqliteStoreError>> {
+    let labels = serde_json::to_string(&record.labels)
+        .map_err(|e| StoreError::InternalStoreError(SqliteStoreError::Serialization(e)))?;
+    statement
+        .execute(params![
+            record.word,
+            record.created_at,
+            record.origin,
+            record.language,
+            record.weight,
+            labels
+        ])
+        .map_err(internal_error)
+}
+
+#[async_trait]
+impl Store for SqliteStore {
+    type E = SqliteStoreError;
+
+    #[tracing::instrument(fields(component = "Sqlite Store"), skip(self))]
+    async fn get_word(&self, word: String) -> Result<WordRecord, StoreError<SqliteStoreError>> {
+        trace!("Getting word {:?} from sqlite store...", word);
+
+        self.with_connection(move |connection| {
+            connection
+                .query_row(
+                    &format!("SELECT {RECORD_COLUMNS} FROM words WHERE word = ?1"),
+                    [&word],
+                    read_record,
+                )
+                .optional()
+                .map_err(internal_error)?
+                .ok_or(StoreError::NotFound(word))
+        })
+        .await
+    }
+
+    #[tracing::instrument(fields(component = "Sqlite Store"), skip(self))]
+    async fn get_random_word(&self) -> Result<String, StoreError<SqliteStoreError>> {
+        trace!("Getting a random word from sqlite store...");
+
+        let index = self
+            .index
+            .read()
+            .map_err(|_| StoreError::InternalStoreError(SqliteStoreError::PoisonedLock))?;
+        Ok(index
+            .sample(&mut rand::rng())
+            .ok_or(StoreError::Empty)?
+            .to_string())
+    }
+
+    #[tracing::instrument(fields(component = "Sqlite Store"), skip(self))]
+    async fn add_word(&mut self, record: WordRecord) -> Result<(), StoreError<SqliteStoreError>> {
+        trace!("Adding word {:?} to sqlite store...", record.word);
+
+        let index = self.index.clone();
+        self.with_connection(move |connection| {
+            let mut statement = connection
+                .prepare_cached(INSERT_RECORD)
+                .map_err(internal_error)?;
+            if insert_record(&mut statement, &record)? == 0 {
+                return Err(StoreError::AlreadyExists(record.word));
+            }
+            Self::with_index(&index, |index| index.insert(record.word, record.weight))
+        })
+        .await
+    }
+
+    #[tracing::instrument(fields(component = "Sqlite Store"), skip(self))]
+    async fn remove_word(&mut self, word: String) -> Result<(), StoreError<SqliteStoreError>> {
+        trace!("Removing word {:?} from sqlite store...", word);
+
+        let index = self.index.clone();
+        self.with_connection(move |connection| {
+            let deleted = connection
+                .execute("DELETE FROM words WHERE word = ?1", params![word])
+                .map_err(internal_error)?;
+            if deleted == 0 {
+                return Err(StoreError::NotFound(word));
+            }
+            Self::with_index(&index, |index| {
+                index.remove(&word);
+            })
+        })
+        .await
+    }
+
+    #[tracing::instrument(fields(component = "Sqlite Store"), skip(self, records))]
+    async fn add_words(
+        &mut self,
+        records: Vec<WordRecord>,
+    ) -> Result<Vec<AddWordStatus>, StoreError<SqliteStoreError>> {
+        trace!("Adding {0} words to sqlite store...", records.len());
+
+        let connection = self.connection.clone();
+        let index = self.index.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut connection = connection
+                .lock()
+                .map_err(|_| StoreError::InternalStoreError(SqliteStoreError::PoisonedLock))?;
+            let transaction = connection.transaction().map_err(internal_error)?;
+            let mut statuses = Vec::with_capacity(records.len());
+            {
+                let mut statement = transaction
+                    .prepare_cached(INSERT_RECORD)
+                    .map_err(internal_error)?;
+                for record in records.iter() {
+                    statuses.push(match insert_record(&mut statement, record)? {
+                        0 => AddWordStatus::AlreadyExists,
+                        _ => AddWordStatus::Created,
+                    });
+                }
+            }
+            transaction.commit().map_err(internal_error)?;
+            Self::with_index(&index, |index| {
+                for (record, status) in records.into_iter().zip(statuses.iter()) {
+                    if *status == AddWordStatus::Created {
+                        index.insert(record.word, record.weight);
+                    }
+                }
+            })?;
+            Ok(statuses)
+        })
+        .await
+        .map_err(|e| StoreError::InternalStoreError(SqliteStoreError::TaskError(e)))?
+    }
+
+    #[tracing::instrument(fields(component = "Sqlite Store"), skip(self))]
+    async fn set_weight(
+        &mut self,
+        word: String,
+        weight: f64,
+    ) -> Result<(), StoreError<SqliteStoreError>> {
+        trace!("Setting weight of word {:?} in sqlite store...", word);
+
+        let index = self.index.clone();
+        self.with_connection(move |connection| {
+            let updated = connection
+                .execute(
+                    "UPDATE words SET weight = ?2 WHERE word = ?1",
+                    params![word, weight],
+                )
+                .map_err(internal_error)?;
+            if updated == 0 {
+                return Err(StoreError::NotFound(word));
+            }
+            Self::with_index(&index, |index| {
+                index.set_weight(&word, weight);
+            })
+        })
+        .await
+    }
+
+    #[tracing::instrument(fields(component = "Sqlite Store"), skip(self))]
+    async fn learn_sentence(
+        &mut self,
+        sentence: Vec<String>,
+    ) -> Result<(), StoreError<SqliteStoreError>> {
+        trace!(
+            "Learning sentence of {0} words in sqlite store...",
+            sentence.len()
+        );
+
+        let connection = self.connection.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut connection = connection
+                .lock()
+                .map_err(|_| StoreError::InternalStoreError(SqliteStoreError::PoisonedLock))?;
+            let transaction = connection.transaction().map_err(internal_error)?;
+            {
+                let mut statement = transaction
+                    .prepare_cached(
+                        "INSERT INTO transitions (context, next, count) VALUES (?1, ?2, 1) \
+                         ON CONFLICT (context, next) DO UPDATE SET count = count + 1",
+                    )
+                    .map_err(internal_error)?;
+                for (context, next) in transitions(&sentence) {
+                    statement
+                        .execute(params![context_key(context)?, next])
+                        .map_err(internal_error)?;
+                }
+            }
+            transaction.commit().map_err(internal_error)
+        })
+        .await
+        .map_err(|e| StoreError::InternalStoreError(SqliteStoreError::TaskError(e)))?
+    }
+
+    #[tracing::instrument(fields(component = "Sqlite Store"), skip(self))]
+    async fn get_next_word(
+        &self,
+        tail: Vec<String>,
+    ) -> Result<Option<String>, StoreError<SqliteStoreError>> {
+        trace!("Getting next word of {:?} from sqlite store...", tail);
+
+        self.with_connection(move |connection| {
+            let mut statement = connection
+                .prepare_cached("SELECT next, count FROM transitions WHERE context = ?1")
+                .map_err(internal_error)?;
+            for context in tail_contexts(&tail) {
+                let successors = statement
+                    .query_map([context_key(context)?], |row| {
+                        Ok((row.get::<_, String>(0)?, row.get::<_, u64>(1)?))
+                    })
+                    .map_err(internal_error)?
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(internal_error)?;
+                if !successors.is_empty() {
+                    return Ok(pick_weighted(
+                        successors.iter().map(|(word, count)| (word, *count)),
+                        &mut rand::rng(),
+                    )
+                    .cloned());
+                }
+            }
+            Ok(None)
+        })
+        .await
+    }
+
+    #[tracing::instrument(fields(component = "Sqlite Store"), skip(self))]
+    async fn list_words(
+        &self,
+        cursor: Option<String>,
+        limit: usize,
+        prefix: Option<String>,
+    ) -> Result<WordPage, StoreError<SqliteStoreError>> {
+        trace!("Listing words of sqlite store...");
+
+        // BINARY collation compares UTF-8 bytes, matching the ordering of Rust strings
+        self.with_connection(move |connection| {
+            let prefix = prefix.unwrap_or_default();
+            let mut statement = connection
+                .prepare_cached(
+                    "SELECT word FROM words \
+                     WHERE (?1 IS NULL OR word > ?1) \
+                     AND word >= ?2 AND substr(word, 1, length(?2)) = ?2 \
+                     ORDER BY word LIMIT ?3",
+                )
+                .map_err(internal_error)?;
+            let words = statement
+                .query_map(params![cursor, prefix, limit as i64 + 1], |row| row.get(0))
+                .map_err(internal_error)?
+                .collect::<Result<Vec<String>, _>>()
+                .map_err(internal_error)?;
+            Ok(WordPage::from_sorted(words, limit))
+        })
+        .await
+    }
+
+    fn iter_words(&self) -> BoxStream<'static, Result<WordRecord, StoreError<SqliteStoreError>>> {
+        trace!("Iterating over words of sqlite store...");
+
+        // Pages through the words by key so that the whole table is never held in memory
+        let store = self.clone();
+        stream::unfold(Some(None), move |cursor: Option<Option<String>>| {
+            let store = store.clone();
+            async move {
+                let cursor = cursor?;
+                let page = store
+                    .with_connection(move |connection| {
+                        let mut statement = connection
+                            .prepare_cached(&format!(
+                                "SELECT {RECORD_COLUMNS} FROM words \
+                                 WHERE (?1 IS NULL OR word > ?1) ORDER BY word LIMIT ?2"
+                            ))
+                            .map_err(internal_error)?;
+                        let records = statement
+                            .query_map(params![cursor, ITER_PAGE_SIZE as i64], read_record)
+                            .map_err(internal_error)?
+                            .collect::<Result<Vec<WordRecord>, _>>()
+                            .map_err(internal_error)?;
+                        Ok(records)
+                    })
+                    .await;
+                match page {
+                    Ok(records) => {
+                        let next_cursor = match records.len() {
+                            ITER_PAGE_SIZE => {
+                                records.last().map(|record| Some(record.word.clone()))
+                            }
+                            _ => None,
+                        };
+                        Some((
+                            stream::iter(records.into_iter().map(Ok)).boxed(),
+                            next_cursor,
+                        ))
+                    }
+                    Err(e) => Some((stream::once(async { Err(e) }).boxed(), None)),
+                }
+            }
+        })
+        .flatten()
+        .boxed()
+    }
+
+    async fn ping(&self) -> Result<(), StoreError<SqliteStoreError>> {
+        self.with_connection(|connection| {
+            connection
+                .query_row("SELECT 1", [], |_| Ok(()))
+                .map_err(internal_error)
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stores::WordMetadata;
+    use tempfile::TempDir;
+
+    fn record(word: &str) -> WordRecord {
+        WordRecord::new(word.to_string(), WordMetadata::default(), "test")
+    }
+
+    fn user_version(connection: &Connection) -> usize {
+        connection
+            .pragma_query_value(None, "user_version", |row| row.get(0))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn older_schemas_are_migrated() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("words.db");
+        {
+            let connection = Connection::open(&path).unwrap();
+            connection.execute_batch(MIGRATIONS[0]).unwrap();
+            connection.pragma_update(None, "user_version", 1).unwrap();
+            connection
+                .execute("INSERT INTO words (word) VALUES ('alpha')", [])
+                .unwrap();
+        }
+
+        let mut store = SqliteStore::open(&path).await.unwrap();
+        let alpha = store.get_word("alpha".to_string()).await.unwrap();
+        assert_eq!(alpha.weight, 1.0);
+        assert_eq!(alpha.origin, "");
+        store
+            .learn_sentence(vec!["alpha".to_string(), "alpha".to_string()])
+            .await
+            .unwrap();
+        assert_eq!(
+            user_version(&store.connection.lock().unwrap()),
+            MIGRATIONS.len()
+        );
+    }
+
+    #[tokio::test]
+    async fn newer_schemas_are_refused() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("words.db");
+        {
+            let connection = Connection::open(&path).unwrap();
+            connection
+                .pragma_update(None, "user_version", MIGRATIONS.len() + 1)
+                .unwrap();
+        }
+
+        assert!(matches!(
+            SqliteStore::open(&path).await,
+            Err(SqliteStoreError::UnsupportedSchemaVersion(version)) if version == MIGRATIONS.len() + 1
+        ));
+    }
+
+    #[tokio::test]
+    async fn list_words_pages_through_the_words_with_a_prefix() {
+        let dir = TempDir::new().unwrap();
+        let mut store = SqliteStore::open(dir.path().join("words.db"))
+            .await
+            .unwrap();
+        store
+            .add_words(
+                ["beta", "apple", "apricot", "avocado", "ant", "banana"]
+                    .map(record)
+                    .to_vec(),
+            )
+            .await
+            .unwrap();
+
+        let page = store
+            .list_words(None, 2, Some("ap".to_string()))
+            .await
+            .unwrap();
+        assert_eq!(page.words, vec!["apple", "apricot"]);
+        assert_eq!(page.next_cursor, None);
+
+        let page = store
+            .list_words(None, 2, Some("a".to_string()))
+            .await
+            .unwrap();
+        assert_eq!(page.words, vec!["ant", "apple"]);
+        let page = store
+            .list_words(page.next_cursor, 2, Some("a".to_string()))
+            .await
+            .unwrap();
+        assert_eq!(page.words, vec!["apricot", "avocado"]);
+        assert_eq!(page.next_cursor, None);
+
+        // A cursor sorting before the prefix starts from the prefix
+        let page = store
+            .list_words(Some("a".to_string()), 5, Some("b".to_string()))
+            .await
+            .unwrap();
+        assert_eq!(page.words, vec!["banana", "beta"]);
+    }
+
+    #[tokio::test]
+    async fn iter_words_goes_through_every_page() {
+        let dir = TempDir::new().unwrap();
+        let mut store = SqliteStore::open(dir.path().join("words.db"))
+            .await
+            .unwrap();
+        let words: Vec<String> = (0..ITER_PAGE_SIZE * 2 + 1)
+            .map(|i| format!("word{i:04}"))
+            .collect();
+        store
+            .add_words(words.iter().map(|word| record(word)).collect())
+            .await
+            .unwrap();
+
+        let listed: Vec<String> = store
+            .iter_words()
+            .map(|record| record.unwrap().word)
+            .collect()
+            .await;
+        assert_eq!(listed, words);
+    }
+
+    #[tokio::test]
+    async fn reopening_rebuilds_the_weighted_index() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("words.db");
+        {
+            let mut store = SqliteStore::open(&path).await.unwrap();
+            store
+                .add_words(vec![record("alpha"), record("beta"), record("gamma")])
+                .await
+                .unwrap();
+            store.remove_word("alpha".to_string()).await.unwrap();
+            store.set_weight("gamma".to_string(), 1000.0).await.unwrap();
+        }
+
+        let store = SqliteStore::open(&path).await.unwrap();
+        let mut gamma_picks = 0;
+        for _ in 0..200 {
+            match store.get_random_word().await.unwrap().as_str() {
+                "gamma" => gamma_picks += 1,
+                word => assert_eq!(word, "beta"),
+            }
+        }
+        assert!(gamma_picks > 150);
+    }
+}
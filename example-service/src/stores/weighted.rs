@@ -0,0 +1,340 @@
+use crate::stores::DEFAULT_WEIGHT;
+use rand::Rng;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::OnceLock;
+
+/// Words laid out in slots with a Fenwick tree over their weights, so that a
+/// weighted random pick, an insertion, a removal or a weight update all run
+/// in O(log n) without walking every word. While every word has the default
+/// weight, random picks skip the tree and index a slot directly in O(1). Otherwise
+/// an alias table is built once as many picks as there are words were made since the
+/// weights last changed, later picks running in O(1) until the next change.
+#[derive(Clone, Debug, Default)]
+pub struct WeightedIndex {
+    words: Vec<String>,
+    weights: Vec<f64>,
+    positions: HashMap<String, usize>,
+    /// Number of slots whose weight differs from [`DEFAULT_WEIGHT`]
+    custom_weights: usize,
+    /// Number of slots with a positive weight, which the tree cannot tell once rounding errors
+    /// piled up in its sums
+    positive_weights: usize,
+    /// Fenwick tree where `tree[i]` holds the sum of the weights of slots `(i - lowbit(i), i]`,
+    /// slots being 1-based in the tree
+    tree: Vec<f64>,
+    alias: LazyAliasTable,
+}
+
+/// Walker's alias table, picking a slot with two random draws whatever the weights.
+#[derive(Clone, Debug)]
+struct AliasTable {
+    /// Probability of keeping the drawn slot rather than moving to its alias
+    probabilities: Vec<f64>,
+    aliases: Vec<usize>,
+}
+
+impl AliasTable {
+    /// Builds the table with Vose's method, `total` being the sum of `weights`.
+    fn new(weights: &[f64], total: f64) -> Self {
+        let len = weights.len();
+        let mut probabilities: Vec<f64> = weights
+            .iter()
+            .map(|weight| weight * len as f64 / total)
+            .collect();
+        let mut aliases: Vec<usize> = (0..len).collect();
+        let (mut small, mut large): (Vec<usize>, Vec<usize>) =
+            (0..len).partition(|slot| probabilities[*slot] < 1.0);
+
+        while let (Some(&less), Some(&more)) = (small.last(), large.last()) {
+            small.pop();
+            aliases[less] = more;
+            probabilities[more] -= 1.0 - probabilities[less];
+            if probabilities[more] < 1.0 {
+                large.pop();
+                small.push(more);
+            }
+        }
+        // Whatever is left is only off 1 by rounding errors, unless it has no weight at all
+        let positive = weights.iter().position(|weight| *weight > 0.0).unwrap_or(0);
+        for slot in small.into_iter().chain(large) {
+            match weights[slot] > 0.0 {
+                true => probabilities[slot] = 1.0,
+                false => {
+                    probabilities[slot] = 0.0;
+                    aliases[slot] = positive;
+                }
+            }
+        }
+
+        AliasTable {
+            probabilities,
+            aliases,
+        }
+    }
+
+    fn sample(&self, rng: &mut impl Rng) -> usize {
+        let slot = rng.random_range(0..self.probabilities.len());
+        match rng.random::<f64>() < self.probabilities[slot] {
+            true => slot,
+            false => self.aliases[slot],
+        }
+    }
+}
+
+/// Alias table of the current weights, built on demand and dropped whenever they change.
+#[derive(Debug, Default)]
+struct LazyAliasTable {
+    /// Picks made through the tree since the weights last changed
+    picks: AtomicUsize,
+    table: OnceLock<AliasTable>,
+}
+
+impl Clone for LazyAliasTable {
+    fn clone(&self) -> Self {
+        LazyAliasTable {
+            picks: AtomicUsize::new(self.picks.load(Ordering::Relaxed)),
+            table: self.table.clone(),
+        }
+    }
+}
+
+fn lowbit(i: usize) -> usize {
+    i & i.wrapping_neg()
+}
+
+impl WeightedIndex {
+    pub fn new() -> Self {
+        WeightedIndex::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    pub fn total_weight(&self) -> f64 {
+        self.prefix_sum(self.words.len())
+    }
+
+    /// Inserts `word` or updates its weight if it is already indexed.
+    pub fn insert(&mut self, word: String, weight: f64) {
+        if let Some(&position) = self.positions.get(&word) {
+            self.update(position, weight);
+            return;
+        }
+
+        let position = self.words.len();
+        let slot = position + 1;
+        // A new last node covers (slot - lowbit(slot), slot], which only depends on existing slots
+        let covered = self.prefix_sum(slot - 1) - self.prefix_sum(slot - lowbit(slot));
+        self.tree.push(covered + weight);
+        if weight != DEFAULT_WEIGHT {
+            self.custom_weights += 1;
+        }
+        if weight > 0.0 {
+            self.positive_weights += 1;
+        }
+        self.positions.insert(word.clone(), position);
+        self.words.push(word);
+        self.weights.push(weight);
+        self.alias = LazyAliasTable::default();
+    }
+
+    /// Updates the weight of `word`, returning `false` if it is not indexed.
+    pub fn set_weight(&mut self, word: &str, weight: f64) -> bool {
+        match self.positions.get(word) {
+            Some(&position) => {
+                self.update(position, weight);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Removes `word` by moving the last slot into its place, returning `false` if it is not indexed.
+    pub fn remove(&mut self, word: &str) -> bool {
+        let Some(position) = self.positions.remove(word) else {
+            return false;
+        };
+
+        let last = self.words.len() - 1;
+        if position != last {
+            let last_weight = self.weights[last];
+            self.update(position, last_weight);
+            self.words.swap(position, last);
+            self.positions
+                .insert(self.words[position].clone(), position);
+        }
+
+        // The last tree node is never part of the range of a lower node, so it can simply be dropped
+        self.words.pop();
+        self.tree.pop();
+        let weight = self.weights.pop().unwrap_or_default();
+        if weight != DEFAULT_WEIGHT {
+            self.custom_weights -= 1;
+        }
+        if weight > 0.0 {
+            self.positive_weights -= 1;
+        }
+        self.alias = LazyAliasTable::default();
+
+        true
+    }
+
+    /// Picks a word with a probability proportional to its weight, or `None`
+    /// when no word has a positive weight.
+    pub fn sample(&self, rng: &mut impl Rng) -> Option<&str> {
+        if self.custom_weights == 0 {
+            return match self.words.len() {
+                0 => None,
+                len => Some(&self.words[rng.random_range(0..len)]),
+            };
+        }
+
+        if let Some(table) = self.alias.table.get() {
+            return Some(&self.words[table.sample(rng)]);
+        }
+
+        let total = self.total_weight();
+        if self.positive_weights == 0 || total <= 0.0 {
+            return None;
+        }
+
+        // Building the table in O(n) pays off once as many picks were made with the same weights
+        if self.alias.picks.fetch_add(1, Ordering::Relaxed) >= self.words.len() {
+            let table = self
+                .alias
+                .table
+                .get_or_init(|| AliasTable::new(&self.weights, self.weights.iter().sum()));
+            return Some(&self.words[table.sample(rng)]);
+        }
+
+        // The sums of the tree drift from the weights as they change, so a target can land on a
+        // slot whose weight is 0, which is drawn again
+        loop {
+            let target = rng.random_range(0.0..total);
+            let position = self.find(target).min(self.words.len() - 1);
+            if self.weights[position] > 0.0 {
+                return Some(&self.words[position]);
+            }
+        }
+    }
+
+    fn update(&mut self, position: usize, weight: f64) {
+        let previous = std::mem::replace(&mut self.weights[position], weight);
+        let delta = weight - previous;
+        match (previous == DEFAULT_WEIGHT, weight == DEFAULT_WEIGHT) {
+            (true, false) => self.custom_weights += 1,
+            (false, true) => self.custom_weights -= 1,
+            _ => {}
+        }
+        match (previous > 0.0, weight > 0.0) {
+            (false, true) => self.positive_weights += 1,
+            (true, false) => self.positive_weights -= 1,
+            _ => {}
+        }
+        self.alias = LazyAliasTable::default();
+
+        let mut slot = position + 1;
+        while slot <= self.tree.len() {
+            self.tree[slot - 1] += delta;
+            slot += lowbit(slot);
+        }
+    }
+
+    fn prefix_sum(&self, slots: usize) -> f64 {
+        let mut sum = 0.0;
+        let mut slot = slots;
+        while slot > 0 {
+            sum += self.tree[slot - 1];
+            slot -= lowbit(slot);
+        }
+        sum
+    }
+
+    /// Returns the position of the first slot whose cumulative weight exceeds `target`.
+    fn find(&self, mut target: f64) -> usize {
+        let mut slot = 0;
+        let mut step = self.tree.len().checked_ilog2().map_or(0, |log| 1 << log);
+        while step > 0 {
+            let next = slot + step;
+            if next <= self.tree.len() && self.tree[next - 1] <= target {
+                slot = next;
+                target -= self.tree[next - 1];
+            }
+            step >>= 1;
+        }
+        slot
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn pick_counts(index: &WeightedIndex, picks: usize) -> HashMap<String, usize> {
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut counts = HashMap::new();
+        for _ in 0..picks {
+            let word = index.sample(&mut rng).unwrap().to_string();
+            *counts.entry(word).or_default() += 1;
+        }
+        counts
+    }
+
+    #[test]
+    fn picks_follow_the_weights_once_the_alias_table_is_built() {
+        let mut index = WeightedIndex::new();
+        index.insert("never".to_string(), 0.0);
+        index.insert("rare".to_string(), 1.0);
+        index.insert("often".to_string(), 3.0);
+
+        let counts = pick_counts(&index, 8000);
+        assert!(index.alias.table.get().is_some());
+        assert_eq!(counts.get("never"), None);
+        let ratio = counts["often"] as f64 / counts["rare"] as f64;
+        assert!((2.7..3.3).contains(&ratio), "ratio was {ratio}");
+
+        // The table follows the weights as they change
+        index.set_weight("often", 0.0);
+        assert!(index.alias.table.get().is_none());
+        assert_eq!(
+            pick_counts(&index, 100).keys().collect::<Vec<_>>(),
+            vec!["rare"]
+        );
+    }
+
+    #[test]
+    fn alias_tables_never_pick_slots_without_weight() {
+        // A total off the sum of the weights leaves slots over once the large ones ran out
+        let table = AliasTable::new(&[0.0, 0.0, 1.0], 2.0);
+        let mut rng = StdRng::seed_from_u64(7);
+        assert!((0..1000).all(|_| table.sample(&mut rng) == 2));
+    }
+
+    #[test]
+    fn words_without_weight_are_never_picked() {
+        let mut index = WeightedIndex::new();
+        for i in 0..100 {
+            index.insert(format!("word{i}"), 0.1 * (i % 7) as f64 + 0.3);
+        }
+        for i in 1..50 {
+            index.set_weight(&format!("word{i}"), 0.0);
+        }
+        for i in 50..100 {
+            index.remove(&format!("word{i}"));
+        }
+
+        // Fewer picks than words, so that they all go through the tree
+        assert_eq!(
+            pick_counts(&index, 49).keys().collect::<Vec<_>>(),
+            vec!["word0"]
+        );
+        assert!(index.alias.table.get().is_none());
+
+        index.set_weight("word0", 0.0);
+        assert_eq!(index.sample(&mut rand::rng()), None);
+    }
+}
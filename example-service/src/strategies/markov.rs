@@ -0,0 +1,69 @@
+use crate::clients::Client;
+use crate::stores::markov::MAX_CONTEXT_LENGTH;
+use crate::stores::{Store, StoreError};
+use crate::strategies::ChainStrategy;
+use tonic::async_trait;
+use tracing::debug;
+
+/// Appends a word learned to follow the last words of the chain, falling back
+/// to a random word when nothing was learned after them.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Markov;
+
+#[async_trait]
+impl<S: Store, C: Client> ChainStrategy<S, C> for Markov {
+    async fn next_word(
+        &self,
+        store: &S,
+        chain: &[String],
+    ) -> Result<Option<String>, StoreError<S::E>> {
+        let tail = chain[chain.len().saturating_sub(MAX_CONTEXT_LENGTH)..].to_vec();
+        if let Some(next_word) = store.get_next_word(tail).await? {
+            return Ok(Some(next_word));
+        }
+
+        debug!("No transition learned after the chain, picking a random word");
+        store.get_random_word().await.map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clients::fake::FakeClient;
+    use crate::stores::hashmap::HashmapStore;
+    use crate::stores::{WordMetadata, WordRecord};
+
+    fn words(words: &[&str]) -> Vec<String> {
+        words.iter().map(|word| word.to_string()).collect()
+    }
+
+    async fn next_word(store: &HashmapStore, chain: &[&str]) -> Option<String> {
+        ChainStrategy::<HashmapStore, FakeClient>::next_word(&Markov, store, &words(chain))
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn next_word_follows_learned_transitions() {
+        let mut store = HashmapStore::from_records(
+            ["one", "two", "three"]
+                .map(|word| WordRecord::new(word.to_string(), WordMetadata::default(), "test")),
+        );
+        store
+            .learn_sentence(words(&["one", "two", "three"]))
+            .await
+            .unwrap();
+
+        for _ in 0..20 {
+            assert_eq!(
+                next_word(&store, &["three", "one", "two"]).await,
+                Some("three".to_string())
+            );
+            assert_eq!(next_word(&store, &["one"]).await, Some("two".to_string()));
+        }
+        // Nothing was learned after the last word, any word of the store fits
+        let next = next_word(&store, &["three"]).await.unwrap();
+        assert!(["one", "two", "three"].contains(&next.as_str()));
+    }
+}
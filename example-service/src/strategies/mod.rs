@@ -0,0 +1,65 @@
+use crate::clients::Client;
+use crate::stores::{Store, StoreError};
+use rand::random_range;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::Arc;
+use tonic::async_trait;
+
+pub mod markov;
+pub mod no_repeat;
+pub mod random;
+pub mod round_robin;
+
+/// Names of the chain strategies, used to select one through the configuration or per request.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum ChainStrategyKind {
+    /// Weighted random words sent to random peers
+    #[default]
+    Random,
+    /// Random words that are not already part of the chain
+    NoRepeat,
+    /// Words learned to follow the end of the chain, or random words when none was learned
+    Markov,
+    /// Random words sent to each peer in turn
+    RoundRobin,
+}
+
+/// Decides how a chain grows on this service: the word appended by each hop,
+/// the peer the chain is forwarded to and when the chain stops.
+#[async_trait]
+pub trait ChainStrategy<S: Store, C: Client>: Debug + Send + Sync {
+    /// Picks the word appended to `chain`, `None` ending the chain without it.
+    async fn next_word(
+        &self,
+        store: &S,
+        chain: &[String],
+    ) -> Result<Option<String>, StoreError<S::E>>;
+
+    /// Picks the index of the peer that `chain` is forwarded to.
+    fn next_peer(&self, peers: &[C], _chain: &[String]) -> Option<usize> {
+        (!peers.is_empty()).then(|| random_range(0..peers.len()))
+    }
+
+    /// Whether `chain` is forwarded to a peer, `remaining` more words being requested.
+    fn should_continue(&self, _chain: &[String], remaining: u32) -> bool {
+        remaining > 0
+    }
+}
+
+pub type Strategies<S, C> = HashMap<ChainStrategyKind, Arc<dyn ChainStrategy<S, C>>>;
+
+/// The implementation of every [`ChainStrategyKind`], new strategies being registered here.
+pub fn builtin_strategies<S: Store, C: Client>() -> Strategies<S, C> {
+    let mut strategies: Strategies<S, C> = HashMap::new();
+    strategies.insert(ChainStrategyKind::Random, Arc::new(random::WeightedRandom));
+    strategies.insert(ChainStrategyKind::NoRepeat, Arc::new(no_repeat::NoRepeat));
+    strategies.insert(ChainStrategyKind::Markov, Arc::new(markov::Markov));
+    strategies.insert(
+        ChainStrategyKind::RoundRobin,
+        Arc::new(round_robin::RoundRobin::default()),
+    );
+    strategies
+}
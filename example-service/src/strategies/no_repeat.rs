@@ -0,0 +1,35 @@
+use crate::clients::Client;
+use crate::stores::{Store, StoreError};
+use crate::strategies::ChainStrategy;
+use tonic::async_trait;
+use tracing::debug;
+
+/// Number of random words drawn before assuming every word is already part of the chain.
+const MAX_ATTEMPTS: usize = 32;
+
+/// Appends a random word that is not already part of the chain, ending the
+/// chain once no such word can be found.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoRepeat;
+
+#[async_trait]
+impl<S: Store, C: Client> ChainStrategy<S, C> for NoRepeat {
+    async fn next_word(
+        &self,
+        store: &S,
+        chain: &[String],
+    ) -> Result<Option<String>, StoreError<S::E>> {
+        for _ in 0..MAX_ATTEMPTS {
+            let word = store.get_random_word().await?;
+            if !chain.contains(&word) {
+                return Ok(Some(word));
+            }
+        }
+
+        debug!(
+            "No new word found after {0} attempts, ending the chain",
+            MAX_ATTEMPTS
+        );
+        Ok(None)
+    }
+}
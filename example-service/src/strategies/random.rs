@@ -0,0 +1,19 @@
+use crate::clients::Client;
+use crate::stores::{Store, StoreError};
+use crate::strategies::ChainStrategy;
+use tonic::async_trait;
+
+/// Appends a random word, biased by word weights, and forwards to a random peer.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WeightedRandom;
+
+#[async_trait]
+impl<S: Store, C: Client> ChainStrategy<S, C> for WeightedRandom {
+    async fn next_word(
+        &self,
+        store: &S,
+        _chain: &[String],
+    ) -> Result<Option<String>, StoreError<S::E>> {
+        store.get_random_word().await.map(Some)
+    }
+}
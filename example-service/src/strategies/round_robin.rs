@@ -0,0 +1,26 @@
+use crate::clients::Client;
+use crate::stores::{Store, StoreError};
+use crate::strategies::ChainStrategy;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tonic::async_trait;
+
+/// Appends a random word and forwards to each peer in turn, spreading chains evenly.
+#[derive(Debug, Default)]
+pub struct RoundRobin {
+    next: AtomicUsize,
+}
+
+#[async_trait]
+impl<S: Store, C: Client> ChainStrategy<S, C> for RoundRobin {
+    async fn next_word(
+        &self,
+        store: &S,
+        _chain: &[String],
+    ) -> Result<Option<String>, StoreError<S::E>> {
+        store.get_random_word().await.map(Some)
+    }
+
+    fn next_peer(&self, peers: &[C], _chain: &[String]) -> Option<usize> {
+        (!peers.is_empty()).then(|| self.next.fetch_add(1, Ordering::Relaxed) % peers.len())
+    }
+}